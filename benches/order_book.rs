@@ -0,0 +1,119 @@
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use market_sim::order_book::{Order, OrderBook, OrderId, OrderSide, Price, Quantity};
+use std::collections::VecDeque;
+use std::hint::black_box;
+
+const MID: Price = 10_000;
+
+// `levels` price levels of `depth` orders each on both sides, one tick apart
+// either side of a one-tick spread around MID
+fn populated_book(levels: i64, depth: usize, quantity: Quantity) -> OrderBook {
+    let mut book = OrderBook::new();
+    for level in 0..levels {
+        for _ in 0..depth {
+            book.add_order(Order::new(OrderSide::Buy, MID - 1 - level, quantity));
+            book.add_order(Order::new(OrderSide::Sell, MID + 1 + level, quantity));
+        }
+    }
+    book
+}
+
+// One bid level `depth` orders deep, with the orders' IDs in queue order
+fn deep_level(depth: usize) -> (OrderBook, Vec<OrderId>) {
+    let mut book = OrderBook::new();
+    let ids = (0..depth)
+        .map(|_| book.add_order(Order::new(OrderSide::Buy, MID - 1, 10)).reports[0].order_id)
+        .collect();
+    (book, ids)
+}
+
+fn bench_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("matching");
+
+    // One large order taking out five levels of 100 orders
+    group.throughput(Throughput::Elements(500));
+    group.bench_function("sweep_500_orders", |b| {
+        b.iter_batched(
+            || populated_book(10, 100, 10),
+            |mut book| black_box(book.add_order(Order::new(OrderSide::Buy, MID + 5, 5_000))),
+            BatchSize::LargeInput,
+        )
+    });
+
+    // A stream of small orders each taking part of the front order of a deep level
+    group.throughput(Throughput::Elements(1_000));
+    group.bench_function("1000_partial_fills", |b| {
+        b.iter_batched(
+            || populated_book(1, 1_000, 10),
+            |mut book| {
+                for i in 0..1_000 {
+                    let side = if i % 2 == 0 { OrderSide::Buy } else { OrderSide::Sell };
+                    let price = if side == OrderSide::Buy { MID + 1 } else { MID - 1 };
+                    black_box(book.add_order(Order::new(side, price, 3)));
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_resting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resting");
+
+    // Orders that do not cross and join the back of their level
+    group.throughput(Throughput::Elements(10_000));
+    group.bench_function("add_10000_passive", |b| {
+        b.iter(|| black_box(populated_book(50, 100, 10)))
+    });
+
+    group.finish();
+}
+
+fn bench_cancels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancels");
+
+    for depth in [1_000, 10_000] {
+        // Every tenth order from the back of the queue to the front
+        group.throughput(Throughput::Elements(depth as u64 / 10));
+        group.bench_function(format!("cancel_spread_through_{depth}_deep_level"), |b| {
+            b.iter_batched(
+                || deep_level(depth),
+                |(mut book, ids)| {
+                    for id in ids.iter().rev().step_by(10) {
+                        black_box(book.cancel_order(*id, Utc::now()));
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    // The order at the front of the queue is cancelled and replaced at the back,
+    // as a quoting strategy moving its orders does
+    group.throughput(Throughput::Elements(1_000));
+    group.bench_function("requote_front_of_10000_deep_level", |b| {
+        b.iter_batched(
+            || {
+                let (book, ids) = deep_level(10_000);
+                (book, VecDeque::from(ids))
+            },
+            |(mut book, mut ids)| {
+                for _ in 0..1_000 {
+                    let id = ids.pop_front().unwrap();
+                    black_box(book.cancel_order(id, Utc::now()));
+                    let execution = book.add_order(Order::new(OrderSide::Buy, MID - 1, 10));
+                    ids.push_back(execution.reports[0].order_id);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_matching, bench_resting, bench_cancels);
+criterion_main!(benches);
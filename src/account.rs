@@ -0,0 +1,143 @@
+use crate::order_book::{AccountId, OrderSide, Price, Quantity, Trade};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Cash amounts use the same fixed-point units as prices
+pub type Cash = i64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Account {
+    pub id: AccountId,
+    pub cash: Cash,
+    // Shares held; negative when short
+    pub position: i64,
+    // What the open position cost, signed like the position
+    pub cost_basis: Cash,
+    // Before fees
+    pub realized_pnl: Cash,
+    // Net trading fees paid; negative when rebates exceed fees
+    pub fees_paid: Cash,
+    // Shares traded in `volume_month`, for volume-tiered fees
+    pub monthly_volume: Quantity,
+    // Calendar month as year * 12 + zero-based month
+    pub volume_month: i32,
+}
+
+impl Account {
+    pub fn new(id: AccountId, cash: Cash) -> Self {
+        Self {
+            id,
+            cash,
+            ..Self::default()
+        }
+    }
+
+    // Average entry price of the open position, in price units
+    pub fn average_price(&self) -> Option<f64> {
+        (self.position != 0).then(|| self.cost_basis as f64 / self.position as f64)
+    }
+
+    pub fn unrealized_pnl(&self, mark_price: Price) -> Cash {
+        self.position.saturating_mul(mark_price).saturating_sub(self.cost_basis)
+    }
+
+    pub fn market_value(&self, mark_price: Price) -> Cash {
+        self.cash.saturating_add(self.position.saturating_mul(mark_price))
+    }
+
+    // Shares traded so far in the calendar month of `at`
+    pub fn volume_in_month(&self, at: DateTime<Utc>) -> Quantity {
+        if self.volume_month == month_index(at) {
+            self.monthly_volume
+        } else {
+            0
+        }
+    }
+
+    // Applies one fill using average-cost accounting: reducing a position realises
+    // PnL against the average entry price, and any excess opens the opposite side
+    fn apply_fill(&mut self, side: OrderSide, price: Price, quantity: Quantity, fee: Cash, at: DateTime<Utc>) {
+        self.monthly_volume = self.volume_in_month(at).saturating_add(quantity);
+        self.volume_month = month_index(at);
+        self.cash = self.cash.saturating_sub(fee);
+        self.fees_paid = self.fees_paid.saturating_add(fee);
+
+        // One fill's value always fits, since every limit price times quantity is
+        // checked on entry; running totals saturate instead of overflowing
+        let quantity = quantity as i64;
+        let signed_quantity = match side {
+            OrderSide::Buy => quantity,
+            OrderSide::Sell => -quantity,
+        };
+        self.cash = self.cash.saturating_sub(signed_quantity * price);
+
+        let mut opening = signed_quantity;
+        if self.position != 0 && self.position.signum() != signed_quantity.signum() {
+            let closing = quantity.min(self.position.abs());
+            let released_cost = (self.cost_basis as i128 * closing as i128 / self.position.abs() as i128) as Cash;
+            self.realized_pnl = self
+                .realized_pnl
+                .saturating_add((self.position.signum() * closing * price).saturating_sub(released_cost));
+            self.cost_basis -= released_cost;
+            self.position -= self.position.signum() * closing;
+            opening = signed_quantity - signed_quantity.signum() * closing;
+            if self.position == 0 {
+                // Drop any rounding residue once flat
+                self.cost_basis = 0;
+            }
+        }
+
+        self.position += opening;
+        self.cost_basis = self.cost_basis.saturating_add(opening * price);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountLedger {
+    accounts: BTreeMap<AccountId, Account>,
+}
+
+impl AccountLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Credits `cash` to the account, creating it if it has not been seen yet. The
+    // balance saturates, like the running totals a fill updates.
+    pub fn deposit(&mut self, account_id: AccountId, cash: Cash) {
+        let account = self.accounts.entry(account_id).or_insert_with(|| Account::new(account_id, 0));
+        account.cash = account.cash.saturating_add(cash);
+    }
+
+    // Updates both counterparties of a trade. Accounts that trade without having
+    // been funded start from zero cash.
+    pub fn apply_trade(&mut self, trade: &Trade) {
+        for (account_id, side) in [
+            (trade.buy_account_id, OrderSide::Buy),
+            (trade.sell_account_id, OrderSide::Sell),
+        ] {
+            self.accounts
+                .entry(account_id)
+                .or_insert_with(|| Account::new(account_id, 0))
+                .apply_fill(side, trade.price, trade.quantity, trade.fee(side), trade.timestamp);
+        }
+    }
+
+    pub fn get_account(&self, account_id: AccountId) -> Option<&Account> {
+        self.accounts.get(&account_id)
+    }
+
+    // Shares the account has traded in the calendar month of `at`
+    pub fn monthly_volume(&self, account_id: AccountId, at: DateTime<Utc>) -> Quantity {
+        self.get_account(account_id).map_or(0, |account| account.volume_in_month(at))
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+}
+
+fn month_index(at: DateTime<Utc>) -> i32 {
+    at.year() * 12 + at.month0() as i32
+}
@@ -0,0 +1,190 @@
+use crate::order_book::{Order, OrderId};
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+// Slot of an order in the arena; valid until that order is removed
+pub type OrderKey = usize;
+
+#[derive(Debug, Clone)]
+struct Slot {
+    order: Order,
+    // Neighbours in the order's queue, if it is in one
+    prev: Option<OrderKey>,
+    next: Option<OrderKey>,
+}
+
+// Single owner of the orders in a book. Price levels and trigger books are
+// `OrderQueue`s threaded through the arena rather than copies, so an order read
+// by ID is always the one the matching engine is working on.
+#[derive(Debug, Clone, Default)]
+pub struct OrderArena {
+    slots: Vec<Option<Slot>>,
+    // Slots of removed orders, reused before the arena grows
+    free: Vec<OrderKey>,
+    keys: HashMap<OrderId, OrderKey>,
+}
+
+impl OrderArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Stores `order`, whose ID must not already be in the arena: the book rejects
+    // duplicates before they get here
+    pub fn insert(&mut self, order: Order) -> OrderKey {
+        debug_assert!(!self.keys.contains_key(&order.id), "order {} is already in the arena", order.id);
+        let id = order.id;
+        let slot = Some(Slot {
+            order,
+            prev: None,
+            next: None,
+        });
+        let key = match self.free.pop() {
+            Some(key) => {
+                self.slots[key] = slot;
+                key
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.keys.insert(id, key);
+        key
+    }
+
+    // Takes the order out of the arena. It must have left its queue first.
+    pub fn remove(&mut self, key: OrderKey) -> Order {
+        let slot = self.slots[key].take().expect("order arena key is stale");
+        debug_assert!(slot.prev.is_none() && slot.next.is_none(), "removed order is still queued");
+        self.keys.remove(&slot.order.id);
+        self.free.push(key);
+        slot.order
+    }
+
+    pub fn key(&self, order_id: &OrderId) -> Option<OrderKey> {
+        self.keys.get(order_id).copied()
+    }
+
+    pub fn get(&self, order_id: &OrderId) -> Option<&Order> {
+        self.key(order_id).map(|key| &self[key])
+    }
+
+    // Both orders at once, e.g. the two sides of an auction trade
+    pub fn pair_mut(&mut self, first: OrderKey, second: OrderKey) -> (&mut Order, &mut Order) {
+        assert_ne!(first, second, "an order cannot trade with itself");
+        let (low, high) = (first.min(second), first.max(second));
+        let (head, tail) = self.slots.split_at_mut(high);
+        let low = &mut head[low].as_mut().expect("order arena key is stale").order;
+        let high = &mut tail[0].as_mut().expect("order arena key is stale").order;
+        if first < second {
+            (low, high)
+        } else {
+            (high, low)
+        }
+    }
+
+    // The order behind `key` in its queue
+    pub fn next(&self, key: OrderKey) -> Option<OrderKey> {
+        self.slot(key).next
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Order> {
+        self.slots.iter().flatten().map(|slot| &slot.order)
+    }
+
+    fn slot(&self, key: OrderKey) -> &Slot {
+        self.slots[key].as_ref().expect("order arena key is stale")
+    }
+
+    fn slot_mut(&mut self, key: OrderKey) -> &mut Slot {
+        self.slots[key].as_mut().expect("order arena key is stale")
+    }
+}
+
+impl Index<OrderKey> for OrderArena {
+    type Output = Order;
+
+    fn index(&self, key: OrderKey) -> &Order {
+        &self.slot(key).order
+    }
+}
+
+impl IndexMut<OrderKey> for OrderArena {
+    fn index_mut(&mut self, key: OrderKey) -> &mut Order {
+        &mut self.slot_mut(key).order
+    }
+}
+
+// Orders at one price in time priority, as a doubly linked list through the
+// arena: joining the back and leaving from anywhere take constant time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OrderQueue {
+    head: Option<OrderKey>,
+    tail: Option<OrderKey>,
+    len: usize,
+}
+
+impl OrderQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<OrderKey> {
+        self.head
+    }
+
+    pub fn push_back(&mut self, arena: &mut OrderArena, key: OrderKey) {
+        let slot = arena.slot_mut(key);
+        slot.prev = self.tail;
+        slot.next = None;
+        match self.tail {
+            Some(tail) => arena.slot_mut(tail).next = Some(key),
+            None => self.head = Some(key),
+        }
+        self.tail = Some(key);
+        self.len += 1;
+    }
+
+    // Unlinks `key`, which must be in this queue
+    pub fn remove(&mut self, arena: &mut OrderArena, key: OrderKey) {
+        let slot = arena.slot_mut(key);
+        let (prev, next) = (slot.prev.take(), slot.next.take());
+        match prev {
+            Some(prev) => arena.slot_mut(prev).next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => arena.slot_mut(next).prev = prev,
+            None => self.tail = prev,
+        }
+        self.len -= 1;
+    }
+
+    pub fn pop_front(&mut self, arena: &mut OrderArena) -> Option<OrderKey> {
+        let key = self.head?;
+        self.remove(arena, key);
+        Some(key)
+    }
+
+    // Keys from the front of the queue to the back
+    pub fn iter<'a>(&self, arena: &'a OrderArena) -> impl Iterator<Item = OrderKey> + 'a {
+        std::iter::successors(self.head, move |&key| arena.next(key))
+    }
+}
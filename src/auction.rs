@@ -0,0 +1,111 @@
+use crate::order_book::{Price, Quantity};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// Trading day of one instrument. Auctions collect orders without matching them
+// and then uncross at a single price; a halted market takes no new orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TradingPhase {
+    PreOpenAuction,
+    Continuous,
+    ClosingAuction,
+    Closed,
+    Halted,
+}
+
+impl TradingPhase {
+    pub fn is_auction(&self) -> bool {
+        matches!(self, TradingPhase::PreOpenAuction | TradingPhase::ClosingAuction)
+    }
+
+    // The day runs closed -> pre-open auction -> continuous -> closing auction ->
+    // closed; either auction may be skipped. Continuous trading can be halted, and
+    // a halt ends in an auction, continuous trading or the close.
+    pub fn can_move_to(&self, next: TradingPhase) -> bool {
+        matches!(
+            (self, next),
+            (TradingPhase::Closed, TradingPhase::PreOpenAuction)
+                | (TradingPhase::Closed, TradingPhase::Continuous)
+                | (TradingPhase::PreOpenAuction, TradingPhase::Continuous)
+                | (TradingPhase::Continuous, TradingPhase::ClosingAuction)
+                | (TradingPhase::Continuous, TradingPhase::Closed)
+                | (TradingPhase::ClosingAuction, TradingPhase::Closed)
+                | (TradingPhase::Continuous, TradingPhase::Halted)
+                | (TradingPhase::Halted, TradingPhase::PreOpenAuction)
+                | (TradingPhase::Halted, TradingPhase::Continuous)
+                | (TradingPhase::Halted, TradingPhase::Closed)
+        )
+    }
+}
+
+impl fmt::Display for TradingPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TradingPhase::PreOpenAuction => "pre-open auction",
+            TradingPhase::Continuous => "continuous trading",
+            TradingPhase::ClosingAuction => "closing auction",
+            TradingPhase::Closed => "closed",
+            TradingPhase::Halted => "halted",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Where the auction would uncross if it ended now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndicativeUncross {
+    pub price: Price,
+    // Quantity that would trade at `price`
+    pub volume: Quantity,
+    // Buy minus sell quantity willing to trade at `price`; positive means buyers
+    // would be left over
+    pub imbalance: i64,
+}
+
+// Picks the uncrossing price among the limit prices in the book, given the
+// quantity left at each bid and ask level: the one that trades the most, then
+// leaves the smallest imbalance. Remaining ties go to the highest price if buyers
+// are left over at all of them, the lowest if sellers are, and otherwise to the
+// price closest to the reference price.
+pub fn find_uncross(
+    bids: &BTreeMap<Price, Quantity>,
+    asks: &BTreeMap<Price, Quantity>,
+    reference_price: Option<Price>,
+) -> Option<IndicativeUncross> {
+    // More volume first, then less imbalance
+    let rank = |c: &IndicativeUncross| (c.volume, Reverse(c.imbalance.abs()));
+
+    let candidates: BTreeSet<Price> = bids.keys().chain(asks.keys()).copied().collect();
+    let mut best: Vec<IndicativeUncross> = Vec::new();
+    for price in candidates {
+        let demand: Quantity = bids.range(price..).map(|(_, quantity)| quantity).sum();
+        let supply: Quantity = asks.range(..=price).map(|(_, quantity)| quantity).sum();
+        let candidate = IndicativeUncross {
+            price,
+            volume: demand.min(supply),
+            imbalance: demand as i64 - supply as i64,
+        };
+        if candidate.volume == 0 {
+            continue;
+        }
+
+        match best.first().map(|current| rank(&candidate).cmp(&rank(current))) {
+            None | Some(Ordering::Greater) => best = vec![candidate],
+            Some(Ordering::Equal) => best.push(candidate),
+            Some(Ordering::Less) => {}
+        }
+    }
+
+    // Candidates were visited in ascending price order
+    if best.iter().all(|c| c.imbalance > 0) {
+        return best.last().copied();
+    }
+    if best.iter().all(|c| c.imbalance < 0) {
+        return best.first().copied();
+    }
+    let (low, high) = (best.first()?.price, best.last()?.price);
+    let reference_price = reference_price.unwrap_or((low + high) / 2);
+    best.into_iter().min_by_key(|c| ((c.price - reference_price).abs(), c.price))
+}
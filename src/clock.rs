@@ -0,0 +1,48 @@
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// Where a MarketSimulator gets the time it stamps on orders, reports and
+// phase changes
+pub trait Clock: fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+// The system clock; the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// Time that only moves when it is told to. Clones share the same time, so a
+// clone kept after handing one to a simulator still drives it.
+#[derive(Debug, Clone)]
+pub struct SimulatedClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
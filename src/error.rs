@@ -0,0 +1,142 @@
+use crate::auction::TradingPhase;
+use crate::order_book::{OrderId, OrderSide, OrderStatus, Price, Quantity};
+use crate::risk::RiskRejection;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// Why the simulator or exchange refused a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketError {
+    // A resting order would leave the spread narrower than the configured minimum
+    SpreadViolation {
+        side: OrderSide,
+        spread_percentage: f64,
+        minimum_spread_percentage: f64,
+    },
+    // Not a finite, positive price
+    InvalidPrice(f64),
+    // More decimal places than the instrument's price scale can represent
+    PriceTooPrecise { price: f64, price_scale: i64 },
+    NotOnTick { price: f64, tick_size: f64 },
+    ZeroQuantity,
+    ZeroPeakQuantity,
+    // Limit price times quantity does not fit in a Cash amount
+    NotionalOverflow { price: Price, quantity: Quantity },
+    UnknownOrder(OrderId),
+    // A new order reused the ID of one still on record or queued
+    DuplicateOrderId(OrderId),
+    OrderAlreadyFilled(OrderId),
+    // Cancelled or expired orders can no longer be changed
+    OrderClosed { order_id: OrderId, status: OrderStatus },
+    // Only resting limit orders can be amended
+    NotAmendable(OrderId),
+    AmendBelowFilled { new_quantity: u64, filled_quantity: u64 },
+    UnknownSymbol(String),
+    DuplicateSymbol(String),
+    EmptySymbol,
+    Risk(RiskRejection),
+    // The request is not accepted in the market's current trading phase
+    NotAllowedInPhase(TradingPhase),
+    InvalidPhaseTransition { from: TradingPhase, to: TradingPhase },
+    // The journal, trade spill file or another store could not be read or written
+    Storage(String),
+    // Replayed journal entries skipped or repeated a sequence number
+    JournalGap { expected: u64, found: u64 },
+    // A market data subscriber missed messages
+    FeedGap { expected: u64, found: u64 },
+    // A snapshot file written in a format this version cannot read
+    UnsupportedSnapshotVersion { found: u64, supported: u64 },
+}
+
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketError::SpreadViolation { side, spread_percentage, minimum_spread_percentage } => write!(
+                f,
+                "{} order would create spread of {:.2}%, minimum required is {:.2}%",
+                match side {
+                    OrderSide::Buy => "Buy",
+                    OrderSide::Sell => "Sell",
+                },
+                spread_percentage,
+                minimum_spread_percentage
+            ),
+            MarketError::InvalidPrice(price) => write!(f, "Invalid price {}", price),
+            MarketError::PriceTooPrecise { price, price_scale } => write!(
+                f,
+                "Price {} has more precision than the instrument supports (1/{})",
+                price, price_scale
+            ),
+            MarketError::NotOnTick { price, tick_size } => {
+                write!(f, "Price {} is not a multiple of the tick size {}", price, tick_size)
+            }
+            MarketError::ZeroQuantity => write!(f, "Quantity must be greater than zero"),
+            MarketError::ZeroPeakQuantity => write!(f, "Iceberg peak quantity must be greater than zero"),
+            MarketError::NotionalOverflow { price, quantity } => {
+                write!(f, "Order value of {} at {} is too large to settle", quantity, price)
+            }
+            MarketError::UnknownOrder(order_id) => write!(f, "Order {} not found", order_id),
+            MarketError::DuplicateOrderId(order_id) => write!(f, "Order ID {} is already in use", order_id),
+            MarketError::OrderAlreadyFilled(order_id) => write!(f, "Order {} is already filled", order_id),
+            MarketError::OrderClosed { order_id, status } => {
+                write!(f, "Order {} is no longer open ({:?})", order_id, status)
+            }
+            MarketError::NotAmendable(order_id) => {
+                write!(f, "Order {} is not a resting limit order and cannot be amended", order_id)
+            }
+            MarketError::AmendBelowFilled { new_quantity, filled_quantity } => write!(
+                f,
+                "New quantity {} must exceed the {} shares already filled",
+                new_quantity, filled_quantity
+            ),
+            MarketError::UnknownSymbol(symbol) => write!(f, "Unknown symbol {}", symbol),
+            MarketError::DuplicateSymbol(symbol) => write!(f, "Symbol {} is already listed", symbol),
+            MarketError::EmptySymbol => write!(f, "Symbol must not be empty"),
+            MarketError::Risk(rejection) => write!(f, "Risk check failed: {}", rejection),
+            MarketError::NotAllowedInPhase(phase) => write!(f, "Not allowed during {}", phase),
+            MarketError::InvalidPhaseTransition { from, to } => {
+                write!(f, "Cannot move from {} to {}", from, to)
+            }
+            MarketError::Storage(message) => write!(f, "Storage error: {}", message),
+            MarketError::JournalGap { expected, found } => {
+                write!(f, "Journal entry {} found where {} was expected", found, expected)
+            }
+            MarketError::FeedGap { expected, found } => {
+                write!(f, "Market data message {} received where {} was expected", found, expected)
+            }
+            MarketError::UnsupportedSnapshotVersion { found, supported } => write!(
+                f,
+                "Snapshot format version {} is not supported (expected {})",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl Error for MarketError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MarketError::Risk(rejection) => Some(rejection),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MarketError {
+    fn from(err: io::Error) -> Self {
+        MarketError::Storage(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for MarketError {
+    fn from(err: serde_json::Error) -> Self {
+        MarketError::Storage(err.to_string())
+    }
+}
+
+impl From<RiskRejection> for MarketError {
+    fn from(rejection: RiskRejection) -> Self {
+        MarketError::Risk(rejection)
+    }
+}
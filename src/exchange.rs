@@ -0,0 +1,146 @@
+use crate::auction::TradingPhase;
+use crate::instrument::Instrument;
+use crate::error::MarketError;
+use crate::execution::ExecutionReport;
+use crate::market::MarketSimulator;
+use crate::order_book::{DepthLevels, Order, OrderId, OrderSide, Quantity};
+use crate::snapshot::{self, MarketSnapshot};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// A venue trading several instruments. Each symbol has its own MarketSimulator,
+// and with it its own order book, trade tape, tick size and spread rules.
+#[derive(Debug, Default)]
+pub struct Exchange {
+    markets: BTreeMap<String, MarketSimulator>,
+}
+
+impl Exchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_instrument(
+        &mut self,
+        symbol: &str,
+        instrument: Instrument,
+        minimum_spread_percentage: f64,
+    ) -> Result<(), MarketError> {
+        let symbol = normalize(symbol);
+        if symbol.is_empty() {
+            return Err(MarketError::EmptySymbol);
+        }
+        if self.markets.contains_key(&symbol) {
+            return Err(MarketError::DuplicateSymbol(symbol));
+        }
+
+        self.markets.insert(
+            symbol,
+            MarketSimulator::with_instrument(minimum_spread_percentage, instrument),
+        );
+        Ok(())
+    }
+
+    // Listed symbols in alphabetical order
+    pub fn symbols(&self) -> Vec<&str> {
+        self.markets.keys().map(String::as_str).collect()
+    }
+
+    pub fn market(&self, symbol: &str) -> Option<&MarketSimulator> {
+        self.markets.get(&normalize(symbol))
+    }
+
+    pub fn market_mut(&mut self, symbol: &str) -> Option<&mut MarketSimulator> {
+        self.markets.get_mut(&normalize(symbol))
+    }
+
+    pub fn place_order(&mut self, symbol: &str, side: OrderSide, price: f64, quantity: Quantity) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.place_order(side, price, quantity)
+    }
+
+    pub fn place_market_order(&mut self, symbol: &str, side: OrderSide, quantity: Quantity) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.place_market_order(side, quantity)
+    }
+
+    pub fn submit_order(&mut self, symbol: &str, order: Order) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.submit_order(order)
+    }
+
+    pub fn amend_order(
+        &mut self,
+        symbol: &str,
+        order_id: OrderId,
+        new_price: f64,
+        new_quantity: Quantity,
+    ) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.amend_order(order_id, new_price, new_quantity)
+    }
+
+    pub fn cancel_order(&mut self, symbol: &str, order_id: OrderId) -> Result<ExecutionReport, MarketError> {
+        self.listed_market(symbol)?.cancel_order(order_id)
+    }
+
+    pub fn set_phase(&mut self, symbol: &str, phase: TradingPhase) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.set_phase(phase)
+    }
+
+    pub fn halt(&mut self, symbol: &str) -> Result<(), MarketError> {
+        self.listed_market(symbol)?.halt()
+    }
+
+    pub fn resume(&mut self, symbol: &str) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.listed_market(symbol)?.resume()
+    }
+
+    pub fn get_order(&self, symbol: &str, order_id: &OrderId) -> Option<&Order> {
+        self.market(symbol)?.get_order(order_id)
+    }
+
+    pub fn get_market_depth(&self, symbol: &str, levels: usize) -> Option<(DepthLevels, DepthLevels)> {
+        Some(self.market(symbol)?.get_market_depth(levels))
+    }
+
+    // Every listed market's state by symbol
+    pub fn snapshot(&self) -> BTreeMap<String, MarketSnapshot> {
+        self.markets
+            .iter()
+            .map(|(symbol, market)| (symbol.clone(), market.snapshot()))
+            .collect()
+    }
+
+    // Lists exactly the symbols in the snapshot. Markets already listed are
+    // restored in place and keep their pluggable parts; new ones start with the
+    // defaults. A market whose spill file cannot be opened stops the restore
+    // with the markets before it already restored.
+    pub fn restore(&mut self, snapshot: BTreeMap<String, MarketSnapshot>) -> Result<(), MarketError> {
+        self.markets.retain(|symbol, _| snapshot.contains_key(symbol));
+        for (symbol, state) in snapshot {
+            match self.markets.get_mut(&symbol) {
+                Some(market) => market.restore(state)?,
+                None => {
+                    self.markets.insert(symbol, MarketSimulator::from_snapshot(state)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MarketError> {
+        snapshot::save(path, &self.snapshot())
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), MarketError> {
+        self.restore(snapshot::load(path)?)
+    }
+
+    fn listed_market(&mut self, symbol: &str) -> Result<&mut MarketSimulator, MarketError> {
+        self.markets
+            .get_mut(&normalize(symbol))
+            .ok_or_else(|| MarketError::UnknownSymbol(symbol.to_string()))
+    }
+}
+
+// Symbols are case-insensitive and stored upper case
+fn normalize(symbol: &str) -> String {
+    symbol.trim().to_uppercase()
+}
@@ -0,0 +1,131 @@
+use crate::halt::Breach;
+use crate::order_book::{AccountId, Liquidity, Order, OrderId, OrderSide, OrderStatus, Price, Quantity, Trade};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionType {
+    // Accepted by the book, either resting, matching or waiting for its stop
+    New,
+    PartialFill,
+    Fill,
+    // Price or quantity changed by an amend
+    Replaced,
+    // Cancelled on request, or the unfilled part of a market, IOC or FOK order
+    Cancelled,
+    // Refused by the book without being accepted
+    Rejected,
+    // Held while the market is halted, to be submitted when it reopens
+    Queued,
+    // Cancelled or reduced instead of trading with an order of the same owner
+    SelfTradePrevented,
+    Expired,
+}
+
+// One event in an order's life, as seen by the order's owner. Prices are in the
+// instrument's fixed-point price units.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub order_id: OrderId,
+    pub account_id: AccountId,
+    pub side: OrderSide,
+    pub execution_type: ExecutionType,
+    // Order status after this event
+    pub status: OrderStatus,
+    // Limit price and total quantity as currently ordered
+    pub price: Price,
+    pub quantity: Quantity,
+    pub cumulative_quantity: Quantity,
+    // Quantity still working; zero once the order is done for any reason
+    pub leaves_quantity: Quantity,
+    // Set on fills only
+    pub last_price: Option<Price>,
+    pub last_quantity: Quantity,
+    pub liquidity: Option<Liquidity>,
+    pub trade_id: Option<Uuid>,
+    // Volume-weighted price of every fill so far
+    pub average_price: Option<f64>,
+    // Set on rejections and self-trade prevention only
+    pub reason: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ExecutionReport {
+    pub fn new(order: &Order, execution_type: ExecutionType, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            order_id: order.id,
+            account_id: order.account_id,
+            side: order.side,
+            execution_type,
+            status: order.status,
+            price: order.price,
+            quantity: order.quantity,
+            cumulative_quantity: order.filled_quantity,
+            leaves_quantity: if order.is_active() { order.remaining_quantity() } else { 0 },
+            last_price: None,
+            last_quantity: 0,
+            liquidity: None,
+            trade_id: None,
+            average_price: order.average_price(),
+            reason: None,
+            timestamp,
+        }
+    }
+
+    // Reports `trade` against `order`, which must already include the fill
+    pub fn fill(order: &Order, trade: &Trade) -> Self {
+        let execution_type = if order.is_complete() {
+            ExecutionType::Fill
+        } else {
+            ExecutionType::PartialFill
+        };
+        Self {
+            last_price: Some(trade.price),
+            last_quantity: trade.quantity,
+            liquidity: Some(trade.liquidity(order.side)),
+            trade_id: Some(trade.id),
+            ..Self::new(order, execution_type, trade.timestamp)
+        }
+    }
+
+    pub fn rejected(order: &Order, reason: &str, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            status: OrderStatus::Rejected,
+            leaves_quantity: 0,
+            reason: Some(reason.to_string()),
+            ..Self::new(order, ExecutionType::Rejected, timestamp)
+        }
+    }
+
+    pub fn is_fill(&self) -> bool {
+        matches!(self.execution_type, ExecutionType::PartialFill | ExecutionType::Fill)
+    }
+}
+
+// Everything one request did to the book: the trades it printed and a report for
+// every order it touched, in the order they happened
+#[derive(Debug, Clone)]
+pub struct Execution {
+    // When the request happened; every trade and report it produced carries it
+    pub timestamp: DateTime<Utc>,
+    pub trades: Vec<Trade>,
+    pub reports: Vec<ExecutionReport>,
+    // Set when a trade tripped the circuit breaker, after which nothing more matched
+    pub breach: Option<Breach>,
+    // What was left of the order being matched when the breaker tripped. The book
+    // neither rests nor cancels it; that is up to the caller.
+    pub interrupted: Option<Order>,
+}
+
+impl Execution {
+    pub fn new(timestamp: DateTime<Utc>) -> Self {
+        Self {
+            timestamp,
+            trades: Vec::new(),
+            reports: Vec::new(),
+            breach: None,
+            interrupted: None,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::account::Cash;
+use crate::order_book::{Liquidity, Price, Quantity};
+use serde::{Deserialize, Serialize};
+
+// A charge per fill. Negative rates are rebates paid to the participant.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FeeRate {
+    // Hundredths of a percent of the fill's notional
+    BasisPoints(f64),
+    // Amount per share, in price units
+    PerShare(f64),
+}
+
+impl FeeRate {
+    // Fee for one fill, rounded to the nearest price unit
+    pub fn amount(&self, price: Price, quantity: Quantity) -> Cash {
+        let amount = match *self {
+            FeeRate::BasisPoints(basis_points) => price as f64 * quantity as f64 * basis_points / 10_000.0,
+            FeeRate::PerShare(per_share) => per_share * quantity as f64,
+        };
+        amount.round() as Cash
+    }
+}
+
+impl Default for FeeRate {
+    fn default() -> Self {
+        FeeRate::BasisPoints(0.0)
+    }
+}
+
+// Rates that replace the base rates once an account's month-to-date volume
+// reaches `minimum_volume` shares
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub minimum_volume: Quantity,
+    pub maker: FeeRate,
+    pub taker: FeeRate,
+}
+
+// Maker/taker pricing applied by MarketSimulator to both legs of every trade.
+// The default schedule charges nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker: FeeRate,
+    pub taker: FeeRate,
+    // Smallest fee charged on a fill, in price units; rebates are not affected
+    pub minimum_fee: Cash,
+    // Kept sorted by minimum volume
+    tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    pub fn new(maker: FeeRate, taker: FeeRate) -> Self {
+        Self {
+            maker,
+            taker,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_minimum_fee(mut self, minimum_fee: Cash) -> Self {
+        self.minimum_fee = minimum_fee;
+        self
+    }
+
+    pub fn with_tier(mut self, minimum_volume: Quantity, maker: FeeRate, taker: FeeRate) -> Self {
+        self.tiers.push(FeeTier { minimum_volume, maker, taker });
+        self.tiers.sort_by_key(|tier| tier.minimum_volume);
+        self
+    }
+
+    pub fn tiers(&self) -> &[FeeTier] {
+        &self.tiers
+    }
+
+    // Rate for an account that has already traded `monthly_volume` shares this month
+    pub fn rate(&self, liquidity: Liquidity, monthly_volume: Quantity) -> FeeRate {
+        let (maker, taker) = self
+            .tiers
+            .iter()
+            .rev()
+            .find(|tier| monthly_volume >= tier.minimum_volume)
+            .map_or((self.maker, self.taker), |tier| (tier.maker, tier.taker));
+        // Auction fills pay the taker rate
+        match liquidity {
+            Liquidity::Maker => maker,
+            Liquidity::Taker | Liquidity::Auction => taker,
+        }
+    }
+
+    pub fn fee(&self, liquidity: Liquidity, price: Price, quantity: Quantity, monthly_volume: Quantity) -> Cash {
+        let fee = self.rate(liquidity, monthly_volume).amount(price, quantity);
+        if fee > 0 {
+            fee.max(self.minimum_fee)
+        } else {
+            fee
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::order_book::Price;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+// What `place_order` does with new orders while the market is halted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HaltedOrderHandling {
+    Reject,
+    // Hold them and submit them in arrival order when the market reopens
+    Queue,
+}
+
+// How a halted market comes back once the halt has run its course
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reopen {
+    // Straight back to continuous trading
+    Continuous,
+    // Through an auction that collects orders for the given time and then uncrosses
+    Auction(Duration),
+}
+
+// Halts trading when a trade moves more than `move_percentage` away from any
+// trade in the preceding `window`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreaker {
+    pub move_percentage: f64,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HaltPolicy {
+    pub order_handling: HaltedOrderHandling,
+    // How long a halt lasts before the market reopens by itself
+    pub halt_duration: Duration,
+    pub reopen: Reopen,
+    // Automatic volatility halts; off unless set
+    pub circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl Default for HaltPolicy {
+    fn default() -> Self {
+        Self {
+            order_handling: HaltedOrderHandling::Reject,
+            halt_duration: Duration::minutes(5),
+            reopen: Reopen::Continuous,
+            circuit_breaker: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HaltReason {
+    Manual,
+    // A trade at `trade_price` moved too far from `reference_price`
+    CircuitBreaker { trade_price: Price, reference_price: Price },
+}
+
+// The trade that tripped the circuit breaker and the earlier price it moved too
+// far from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Breach {
+    pub trade_id: Uuid,
+    pub trade_price: Price,
+    pub reference_price: Price,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Halt {
+    pub reason: HaltReason,
+    pub halted_at: DateTime<Utc>,
+    pub resumes_at: DateTime<Utc>,
+}
+
+// Trade prices seen within the circuit breaker's rolling window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceWindow {
+    prices: VecDeque<(DateTime<Utc>, Price)>,
+}
+
+impl PriceWindow {
+    // Adds a trade and returns the earlier price in the window it moved furthest
+    // from, if that move is beyond the breaker's limit
+    pub fn record(&mut self, breaker: &CircuitBreaker, at: DateTime<Utc>, price: Price) -> Option<Price> {
+        while self.prices.front().is_some_and(|&(time, _)| time < at - breaker.window) {
+            self.prices.pop_front();
+        }
+
+        let reference_price = self
+            .prices
+            .iter()
+            .map(|&(_, reference)| reference)
+            .max_by_key(|reference| (price - reference).abs());
+        self.prices.push_back((at, price));
+
+        reference_price.filter(|&reference| {
+            (price - reference).abs() as f64 / reference as f64 * 100.0 > breaker.move_percentage
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.prices.clear();
+    }
+}
@@ -0,0 +1,191 @@
+use crate::order_book::{Order, OrderId, Trade};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+// How much history MarketSimulator keeps in memory. The default keeps
+// everything, as the simulator always has.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    // Move filled, cancelled and expired orders out of the live order index and
+    // into the order archive, if there is one
+    pub evict_closed_orders: bool,
+    // Most trades kept on the in-memory tape; unlimited when `None`
+    pub trade_capacity: Option<usize>,
+    // File that trades beyond the capacity are appended to as JSON lines; they
+    // are dropped without one
+    pub trade_spill_path: Option<PathBuf>,
+}
+
+// Where evicted orders go. Lookups return copies, so an archive may keep its
+// orders anywhere.
+pub trait OrderArchive: fmt::Debug {
+    fn archive(&mut self, order: Order);
+
+    fn get(&self, order_id: &OrderId) -> Option<Order>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOrderArchive {
+    orders: HashMap<OrderId, Order>,
+}
+
+impl MemoryOrderArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+impl OrderArchive for MemoryOrderArchive {
+    fn archive(&mut self, order: Order) {
+        self.orders.insert(order.id, order);
+    }
+
+    fn get(&self, order_id: &OrderId) -> Option<Order> {
+        self.orders.get(order_id).cloned()
+    }
+}
+
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl SpillFile {
+    // Appends one trade as a whole line. A write that fails part way is cut back
+    // off, so the file only ever holds complete lines.
+    fn append(&mut self, trade: &Trade) -> io::Result<()> {
+        let mut line = serde_json::to_vec(trade)?;
+        line.push(b'\n');
+        let end = self.file.metadata()?.len();
+        self.file.write_all(&line).inspect_err(|_| {
+            let _ = self.file.set_len(end);
+        })
+    }
+}
+
+// The trade tape: the most recent trades in memory, oldest first, and with a
+// capacity set the older ones in a spill file or gone
+#[derive(Debug, Default)]
+pub struct TradeTape {
+    recent: VecDeque<Trade>,
+    capacity: Option<usize>,
+    spill: Option<SpillFile>,
+    // Trades no longer in memory, spilled or dropped
+    evicted: usize,
+    // Buyer-initiated minus seller-initiated volume of every trade ever added
+    signed_volume: i64,
+    // Once the spill file fails to be written, trades are no longer spilled and
+    // stay in memory until the tape is configured again
+    error: Option<String>,
+}
+
+impl TradeTape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Applies a new capacity and spill file, evicting at once if the tape is over
+    // the capacity. An existing spill file is appended to. Clears any earlier
+    // spill error.
+    pub fn configure(&mut self, capacity: Option<usize>, spill_path: Option<&Path>) -> io::Result<()> {
+        self.spill = match spill_path {
+            Some(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                Some(SpillFile {
+                    path: path.to_path_buf(),
+                    file,
+                })
+            }
+            None => None,
+        };
+        self.capacity = capacity;
+        self.error = None;
+        self.evict()
+    }
+
+    // Adds a trade, spilling the oldest ones beyond the capacity. If the spill
+    // file cannot be written, the trades stay in memory and the failure is kept
+    // in `error`.
+    pub fn push(&mut self, trade: Trade) {
+        self.signed_volume += trade.signed_quantity();
+        self.recent.push_back(trade);
+        let _ = self.evict();
+    }
+
+    fn evict(&mut self) -> io::Result<()> {
+        if let Some(error) = &self.error {
+            return Err(io::Error::other(error.clone()));
+        }
+        let Some(capacity) = self.capacity else {
+            return Ok(());
+        };
+        while self.recent.len() > capacity {
+            if let Some(spill) = &mut self.spill {
+                if let Err(error) = spill.append(&self.recent[0]) {
+                    self.error = Some(error.to_string());
+                    return Err(error);
+                }
+            }
+            self.recent.pop_front();
+            self.evicted += 1;
+        }
+        Ok(())
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    // Trades still in memory, oldest first
+    pub fn recent(&self) -> &VecDeque<Trade> {
+        &self.recent
+    }
+
+    // Every trade ever added
+    pub fn total(&self) -> usize {
+        self.evicted + self.recent.len()
+    }
+
+    // Trades no longer in memory
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    // Puts back a tape saved earlier, keeping the current capacity and spill file
+    pub fn restore(&mut self, recent: Vec<Trade>, evicted: usize, signed_volume: i64) -> io::Result<()> {
+        self.recent = recent.into();
+        self.evicted = evicted;
+        self.signed_volume = signed_volume;
+        self.evict()
+    }
+
+    pub fn signed_volume(&self) -> i64 {
+        self.signed_volume
+    }
+
+    // Every trade still available, oldest first: the spill file followed by the
+    // trades in memory. Trades dropped without a spill file are missing.
+    pub fn history(&self) -> io::Result<Vec<Trade>> {
+        let mut trades = Vec::new();
+        if let Some(spill) = &self.spill {
+            for line in BufReader::new(File::open(&spill.path)?).lines() {
+                trades.push(serde_json::from_str(&line?)?);
+            }
+        }
+        trades.extend(self.recent.iter().cloned());
+        Ok(trades)
+    }
+}
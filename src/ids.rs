@@ -0,0 +1,93 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::{Builder, Uuid};
+
+// Source of the IDs a simulator gives its orders and trades
+pub trait IdGenerator: fmt::Debug {
+    fn next_id(&self) -> Uuid;
+
+    // How far through its sequence the generator is, for snapshots; `None` for a
+    // generator with no sequence to keep
+    fn position(&self) -> Option<u64> {
+        None
+    }
+
+    // Carries on from a position given by `position`
+    fn resume_from(&self, _position: u64) {}
+}
+
+// Random version 4 UUIDs; the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+// 1, 2, 3, ... as UUIDs, which keeps IDs short and readable in test output
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    last: AtomicU64,
+}
+
+impl SequentialIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::from_u128(self.last.fetch_add(1, Ordering::Relaxed) as u128 + 1)
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.last.load(Ordering::Relaxed))
+    }
+
+    fn resume_from(&self, position: u64) {
+        self.last.store(position, Ordering::Relaxed);
+    }
+}
+
+// Random-looking version 4 UUIDs from a seeded SplitMix64 stream: the same seed
+// gives the same IDs in the same order
+#[derive(Debug)]
+pub struct SeededIds {
+    state: AtomicU64,
+}
+
+impl SeededIds {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        let mut z = self
+            .state
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl IdGenerator for SeededIds {
+    fn next_id(&self) -> Uuid {
+        let bits = (self.next_u64() as u128) << 64 | self.next_u64() as u128;
+        Builder::from_random_bytes(bits.to_be_bytes()).into_uuid()
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.state.load(Ordering::Relaxed))
+    }
+
+    fn resume_from(&self, position: u64) {
+        self.state.store(position, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::error::MarketError;
+use crate::order_book::Price;
+use serde::{Deserialize, Serialize};
+
+// Prices are fixed-point integers: a price of 10_050 with a price scale of 100
+// means 100.50. Only multiples of the tick size are valid order prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    // Integer price units per whole currency unit, e.g. 100 for cents
+    pub price_scale: i64,
+    // Smallest allowed price increment, in price units
+    pub tick_size: Price,
+}
+
+impl Instrument {
+    pub fn new(price_scale: i64, tick_size: Price) -> Self {
+        assert!(price_scale > 0, "price scale must be positive");
+        assert!(tick_size > 0, "tick size must be positive");
+        Self { price_scale, tick_size }
+    }
+
+    pub fn is_on_tick(&self, price: Price) -> bool {
+        price % self.tick_size == 0
+    }
+
+    // Order prices must be positive and sit on the tick grid
+    pub fn validate_price(&self, price: Price) -> Result<(), MarketError> {
+        if price <= 0 {
+            return Err(MarketError::InvalidPrice(self.to_f64(price)));
+        }
+        if !self.is_on_tick(price) {
+            return Err(MarketError::NotOnTick {
+                price: self.to_f64(price),
+                tick_size: self.to_f64(self.tick_size),
+            });
+        }
+        Ok(())
+    }
+
+    // Converts a decimal price to price units, rejecting anything that is not
+    // exactly representable at this scale or does not sit on the tick grid
+    pub fn to_price(&self, value: f64) -> Result<Price, MarketError> {
+        if !value.is_finite() {
+            return Err(MarketError::InvalidPrice(value));
+        }
+
+        let scaled = value * self.price_scale as f64;
+        // Beyond what price units can hold; `as` would saturate it onto a valid price
+        if !(Price::MIN as f64..Price::MAX as f64).contains(&scaled) {
+            return Err(MarketError::InvalidPrice(value));
+        }
+        let price = scaled.round() as Price;
+        // Tolerate binary floating point noise such as 100.1 * 100 = 10009.999...
+        if (scaled - price as f64).abs() > 1e-6 {
+            return Err(MarketError::PriceTooPrecise {
+                price: value,
+                price_scale: self.price_scale,
+            });
+        }
+        self.validate_price(price)?;
+
+        Ok(price)
+    }
+
+    pub fn to_f64(&self, price: Price) -> f64 {
+        price as f64 / self.price_scale as f64
+    }
+
+    // Decimal places needed to show any price at this scale
+    pub fn decimals(&self) -> usize {
+        let mut decimals = 0;
+        let mut scale = self.price_scale;
+        while scale > 1 {
+            scale /= 10;
+            decimals += 1;
+        }
+        decimals
+    }
+
+    pub fn format_price(&self, price: Price) -> String {
+        format!("{:.*}", self.decimals(), self.to_f64(price))
+    }
+}
+
+// Cent prices with a one cent tick
+impl Default for Instrument {
+    fn default() -> Self {
+        Self::new(100, 1)
+    }
+}
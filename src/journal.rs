@@ -0,0 +1,114 @@
+use crate::account::Cash;
+use crate::auction::TradingPhase;
+use crate::order_book::{AccountId, Order, OrderId, Price, Quantity};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// A request that changes a MarketSimulator's state, in the form it was applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    // Carries the whole order, ID and timestamp included, so replay submits the
+    // same order
+    Submit(Order),
+    Cancel(OrderId),
+    Amend { order_id: OrderId, price: Price, quantity: Quantity },
+    SetPhase(TradingPhase),
+    Resume,
+    Update(DateTime<Utc>),
+    ExpireOrders(DateTime<Utc>),
+    EndOfDay,
+    Deposit { account_id: AccountId, cash: Cash },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    // Starts at 1 and goes up by one per command
+    pub sequence: u64,
+    // When the command was applied; phase changes and halts replay at this time
+    pub timestamp: DateTime<Utc>,
+    pub command: Command,
+}
+
+// Append-only record of the commands applied to a simulator, kept in memory or
+// written to a file as JSON lines
+#[derive(Debug)]
+pub struct Journal {
+    last_sequence: u64,
+    entries: Vec<JournalEntry>,
+    file: Option<BufWriter<File>>,
+    // Once an entry fails to be written the journal no longer matches the
+    // market, so every later entry is refused too
+    error: Option<String>,
+}
+
+impl Journal {
+    pub fn in_memory() -> Self {
+        Self {
+            last_sequence: 0,
+            entries: Vec::new(),
+            file: None,
+            error: None,
+        }
+    }
+
+    // Starts a new journal file, replacing any file at `path`. Entries are flushed
+    // as they are written and not kept in memory.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: Some(BufWriter::new(File::create(path)?)),
+            ..Self::in_memory()
+        })
+    }
+
+    // Entries of a journal file, in the order they were written
+    pub fn read(path: &Path) -> io::Result<Vec<JournalEntry>> {
+        BufReader::new(File::open(path)?)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+
+    // Entries of an in-memory journal; empty for a journal written to a file
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn record(&mut self, timestamp: DateTime<Utc>, command: Command) -> io::Result<u64> {
+        if let Some(error) = &self.error {
+            return Err(io::Error::other(error.clone()));
+        }
+
+        let entry = JournalEntry {
+            sequence: self.last_sequence + 1,
+            timestamp,
+            command,
+        };
+        match &mut self.file {
+            Some(file) => {
+                let written = serde_json::to_writer(&mut *file, &entry)
+                    .map_err(io::Error::from)
+                    .and_then(|()| file.write_all(b"\n"))
+                    .and_then(|()| file.flush());
+                if let Err(error) = written {
+                    self.error = Some(error.to_string());
+                    return Err(error);
+                }
+            }
+            None => self.entries.push(entry),
+        }
+
+        self.last_sequence += 1;
+        Ok(self.last_sequence)
+    }
+}
@@ -0,0 +1,20 @@
+pub mod account;
+pub mod arena;
+pub mod auction;
+pub mod clock;
+pub mod error;
+pub mod exchange;
+pub mod execution;
+pub mod fees;
+pub mod halt;
+pub mod history;
+pub mod ids;
+pub mod instrument;
+pub mod journal;
+pub mod order_book;
+pub mod market;
+pub mod market_data;
+pub mod matching;
+pub mod observer;
+pub mod risk;
+pub mod snapshot;
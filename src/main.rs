@@ -1,43 +1,43 @@
-mod order_book;
-mod market;
-
-use crate::market::MarketSimulator;
-use crate::order_book::OrderSide;
+use market_sim::auction::TradingPhase;
+use market_sim::exchange::Exchange;
+use market_sim::execution::{ExecutionReport, ExecutionType};
+use market_sim::instrument::Instrument;
+use market_sim::order_book::{OrderSide, Price};
 use std::io::{self, Write};
 
 fn main() {
     println!("=== Market Simulator ===");
     println!("Minimum spread: 1%");
-    println!("Commands:");
-    println!("  buy <price> <quantity>  - Place buy limit order");
-    println!("  sell <price> <quantity> - Place sell limit order");
-    println!("  cancel <order_id>       - Cancel order");
-    println!("  status                  - Show market status");
-    println!("  quit                    - Exit");
+    print_help();
     println!();
 
-    let mut market = MarketSimulator::new(1.0); // 1% minimum spread
+    let mut exchange = Exchange::new();
+    for symbol in ["AAPL", "MSFT"] {
+        exchange.add_instrument(symbol, Instrument::default(), 1.0).unwrap(); // 1% minimum spread
+    }
 
     // Add some initial orders to demonstrate the market
     println!("Adding some initial orders...");
-    
-    // Initial buy orders
-    if let Ok(order_id) = market.place_order(OrderSide::Buy, 100.0, 10) {
-        println!("Placed initial buy order: {} shares at $100.00 (ID: {})", 10, order_id);
-    }
-    if let Ok(order_id) = market.place_order(OrderSide::Buy, 99.0, 15) {
-        println!("Placed initial buy order: {} shares at $99.00 (ID: {})", 15, order_id);
-    }
 
-    // Initial sell orders (must be at least 1% spread from buy orders)
-    if let Ok(order_id) = market.place_order(OrderSide::Sell, 102.0, 8) {
-        println!("Placed initial sell order: {} shares at $102.00 (ID: {})", 8, order_id);
-    }
-    if let Ok(order_id) = market.place_order(OrderSide::Sell, 103.0, 12) {
-        println!("Placed initial sell order: {} shares at $103.00 (ID: {})", 12, order_id);
+    // Initial sell orders must be at least 1% away from the buy orders
+    let initial_orders = [
+        ("AAPL", OrderSide::Buy, 100.0, 10),
+        ("AAPL", OrderSide::Buy, 99.0, 15),
+        ("AAPL", OrderSide::Sell, 102.0, 8),
+        ("AAPL", OrderSide::Sell, 103.0, 12),
+        ("MSFT", OrderSide::Buy, 300.0, 5),
+        ("MSFT", OrderSide::Sell, 305.0, 5),
+    ];
+    for (symbol, side, price, quantity) in initial_orders {
+        if let Ok(reports) = exchange.place_order(symbol, side, price, quantity) {
+            println!(
+                "Placed initial {} {} order: {} shares at ${:.2} (ID: {})",
+                symbol, side_name(side), quantity, price, reports[0].order_id
+            );
+        }
     }
 
-    market.print_market_status();
+    print_status(&exchange, None);
 
     loop {
         print!("> ");
@@ -53,32 +53,52 @@ fn main() {
 
         let parts: Vec<&str> = input.split_whitespace().collect();
         
-        match parts.get(0) {
+        match parts.first() {
             Some(&"quit") | Some(&"exit") => {
                 println!("Goodbye!");
                 break;
             }
             Some(&"status") => {
-                market.print_market_status();
+                print_status(&exchange, parts.get(1).copied());
             }
-            Some(&"buy") => {
-                if parts.len() != 3 {
-                    println!("Usage: buy <price> <quantity>");
+            Some(&"symbols") => {
+                for symbol in exchange.symbols() {
+                    let market = exchange.market(symbol).unwrap();
+                    let instrument = market.instrument();
+                    let quote = |price: Option<Price>| price.map_or("-".to_string(), |p| instrument.format_price(p));
+                    println!(
+                        "{}\tbid {}\task {}\ttick {}",
+                        symbol,
+                        quote(market.get_best_bid()),
+                        quote(market.get_best_ask()),
+                        instrument.format_price(instrument.tick_size)
+                    );
+                }
+            }
+            Some(&"buy") | Some(&"sell") => {
+                let side = if parts[0] == "buy" { OrderSide::Buy } else { OrderSide::Sell };
+                if parts.len() != 4 {
+                    println!("Usage: {} <symbol> <price> <quantity>  or  {} <symbol> market <quantity>", parts[0], parts[0]);
                     continue;
                 }
-                
-                match (parts[1].parse::<f64>(), parts[2].parse::<u64>()) {
+
+                let symbol = parts[1];
+                if parts[2] == "market" {
+                    match parts[3].parse::<u64>() {
+                        Ok(quantity) => place_market_order(&mut exchange, symbol, side, quantity),
+                        Err(_) => println!("Invalid quantity"),
+                    }
+                    continue;
+                }
+
+                match (parts[2].parse::<f64>(), parts[3].parse::<u64>()) {
                     (Ok(price), Ok(quantity)) => {
-                        match market.place_order(OrderSide::Buy, price, quantity) {
-                            Ok(order_id) => {
-                                println!("Buy order placed: {} shares at ${:.2} (ID: {})", quantity, price, order_id);
+                        match exchange.place_order(symbol, side, price, quantity) {
+                            Ok(reports) => {
+                                println!("{} order placed: {} {} shares at ${:.2} (ID: {})", side_name(side), symbol, quantity, price, reports[0].order_id);
                                 
-                                // Show any trades that occurred
-                                let recent_trades = market.get_recent_trades(1);
-                                if !recent_trades.is_empty() {
-                                    let trade = recent_trades[0];
-                                    println!("🔥 TRADE EXECUTED: {} shares at ${:.2}", trade.quantity, trade.price.into_inner());
-                                }
+                                // Show what happened to the order
+                                print_reports(&exchange, symbol, &reports);
                             }
                             Err(msg) => println!("Error: {}", msg),
                         }
@@ -86,64 +106,114 @@ fn main() {
                     _ => println!("Invalid price or quantity"),
                 }
             }
-            Some(&"sell") => {
+            Some(&"cancel") => {
                 if parts.len() != 3 {
-                    println!("Usage: sell <price> <quantity>");
+                    println!("Usage: cancel <symbol> <order_id>");
                     continue;
                 }
                 
-                match (parts[1].parse::<f64>(), parts[2].parse::<u64>()) {
-                    (Ok(price), Ok(quantity)) => {
-                        match market.place_order(OrderSide::Sell, price, quantity) {
-                            Ok(order_id) => {
-                                println!("Sell order placed: {} shares at ${:.2} (ID: {})", quantity, price, order_id);
-                                
-                                // Show any trades that occurred
-                                let recent_trades = market.get_recent_trades(1);
-                                if !recent_trades.is_empty() {
-                                    let trade = recent_trades[0];
-                                    println!("🔥 TRADE EXECUTED: {} shares at ${:.2}", trade.quantity, trade.price.into_inner());
-                                }
+                match parts[2].parse::<uuid::Uuid>() {
+                    Ok(order_id) => {
+                        match exchange.cancel_order(parts[1], order_id) {
+                            Ok(report) => {
+                                let instrument = exchange.market(parts[1]).unwrap().instrument();
+                                println!("Cancelled order: {} {} {} {} shares at ${}", 
+                                    side_name(report.side).to_uppercase(),
+                                    parts[1].to_uppercase(),
+                                    report.quantity - report.cumulative_quantity,
+                                    report.quantity,
+                                    instrument.format_price(report.price)
+                                );
                             }
-                            Err(msg) => println!("Error: {}", msg),
+                            Err(err) => println!("Error: {}", err),
                         }
                     }
-                    _ => println!("Invalid price or quantity"),
+                    Err(_) => println!("Invalid order ID format"),
                 }
             }
-            Some(&"cancel") => {
-                if parts.len() != 2 {
-                    println!("Usage: cancel <order_id>");
+            Some(&"amend") => {
+                if parts.len() != 5 {
+                    println!("Usage: amend <symbol> <order_id> <price> <quantity>");
                     continue;
                 }
-                
-                match parts[1].parse::<uuid::Uuid>() {
-                    Ok(order_id) => {
-                        match market.cancel_order(order_id) {
-                            Some(order) => {
-                                println!("Cancelled order: {} {} {} shares at ${:.2}", 
-                                    match order.side {
-                                        OrderSide::Buy => "BUY",
-                                        OrderSide::Sell => "SELL",
-                                    },
-                                    order.remaining_quantity(),
-                                    order.quantity,
-                                    order.price.into_inner()
-                                );
+
+                let symbol = parts[1];
+                match (parts[2].parse::<uuid::Uuid>(), parts[3].parse::<f64>(), parts[4].parse::<u64>()) {
+                    (Ok(order_id), Ok(price), Ok(quantity)) => {
+                        match exchange.amend_order(symbol, order_id, price, quantity) {
+                            Ok(reports) => {
+                                println!("Amended order {}: {} shares at ${:.2}", order_id, quantity, price);
+                                print_reports(&exchange, symbol, &reports);
                             }
-                            None => println!("Order not found"),
+                            Err(msg) => println!("Error: {}", msg),
                         }
                     }
-                    Err(_) => println!("Invalid order ID format"),
+                    _ => println!("Invalid order ID, price or quantity"),
+                }
+            }
+            Some(&"phase") => {
+                let phase = match parts.get(2).copied() {
+                    Some("preopen") => TradingPhase::PreOpenAuction,
+                    Some("continuous") => TradingPhase::Continuous,
+                    Some("closing") => TradingPhase::ClosingAuction,
+                    Some("closed") => TradingPhase::Closed,
+                    _ => {
+                        println!("Usage: phase <symbol> <preopen|continuous|closing|closed>");
+                        continue;
+                    }
+                };
+
+                let symbol = parts[1];
+                match exchange.set_phase(symbol, phase) {
+                    Ok(reports) => {
+                        println!("{} phase: {}", symbol.to_uppercase(), phase);
+                        let instrument = exchange.market(symbol).unwrap().instrument();
+                        for report in reports.iter().filter(|r| r.is_fill() && r.side == OrderSide::Buy) {
+                            println!(
+                                "🔥 TRADE EXECUTED: {} shares at ${}",
+                                report.last_quantity,
+                                instrument.format_price(report.last_price.unwrap_or_default())
+                            );
+                        }
+                    }
+                    Err(msg) => println!("Error: {}", msg),
+                }
+            }
+            Some(&"halt") | Some(&"resume") => {
+                let Some(&symbol) = parts.get(1) else {
+                    println!("Usage: {} <symbol>", parts[0]);
+                    continue;
+                };
+
+                let result = if parts[0] == "halt" {
+                    exchange.halt(symbol).map(|()| Vec::new())
+                } else {
+                    exchange.resume(symbol)
+                };
+                match result {
+                    Ok(_) => println!("{} phase: {}", symbol.to_uppercase(), exchange.market(symbol).unwrap().phase()),
+                    Err(msg) => println!("Error: {}", msg),
+                }
+            }
+            Some(&"save") | Some(&"load") => {
+                let Some(&file) = parts.get(1) else {
+                    println!("Usage: {} <file>", parts[0]);
+                    continue;
+                };
+
+                let path = std::path::Path::new(file);
+                let result = if parts[0] == "save" { exchange.save(path) } else { exchange.load(path) };
+                match result {
+                    Ok(()) if parts[0] == "save" => println!("Saved {} symbols to {}", exchange.symbols().len(), file),
+                    Ok(()) => {
+                        println!("Loaded {} symbols from {}", exchange.symbols().len(), file);
+                        print_status(&exchange, None);
+                    }
+                    Err(msg) => println!("Error: {}", msg),
                 }
             }
             Some(&"help") => {
-                println!("Commands:");
-                println!("  buy <price> <quantity>  - Place buy limit order");
-                println!("  sell <price> <quantity> - Place sell limit order");
-                println!("  cancel <order_id>       - Cancel order");
-                println!("  status                  - Show market status");
-                println!("  quit                    - Exit");
+                print_help();
             }
             _ => {
                 println!("Unknown command. Type 'help' for available commands.");
@@ -152,20 +222,139 @@ fn main() {
     }
 }
 
+fn print_help() {
+    println!("Commands:");
+    println!("  buy <symbol> <price> <quantity>  - Place buy limit order");
+    println!("  sell <symbol> <price> <quantity> - Place sell limit order");
+    println!("  buy <symbol> market <quantity>   - Place buy market order");
+    println!("  sell <symbol> market <quantity>  - Place sell market order");
+    println!("  cancel <symbol> <order_id>       - Cancel order");
+    println!("  amend <symbol> <order_id> <price> <quantity> - Change price/quantity of an order");
+    println!("  phase <symbol> <preopen|continuous|closing|closed> - Change trading phase");
+    println!("  halt <symbol>                    - Halt trading");
+    println!("  resume <symbol>                  - Reopen a halted symbol");
+    println!("  status [symbol]                  - Show market status");
+    println!("  symbols                          - List listed symbols");
+    println!("  save <file>                      - Save every symbol's state to a file");
+    println!("  load <file>                      - Restore the state saved in a file");
+    println!("  quit                             - Exit");
+}
+
+fn side_name(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "Buy",
+        OrderSide::Sell => "Sell",
+    }
+}
+
+fn print_status(exchange: &Exchange, symbol: Option<&str>) {
+    let symbols = match symbol {
+        Some(symbol) => vec![symbol],
+        None => exchange.symbols(),
+    };
+
+    for symbol in symbols {
+        match exchange.market(symbol) {
+            Some(market) => {
+                println!("\n[{}]", symbol.to_uppercase());
+                market.print_market_status();
+            }
+            None => println!("Unknown symbol {}", symbol),
+        }
+    }
+}
+
+// Walks the execution reports of the order that was just sent. An order can
+// sweep several price levels, so every one of its fills is shown.
+fn print_reports(exchange: &Exchange, symbol: &str, reports: &[ExecutionReport]) {
+    let (Some(market), Some(first)) = (exchange.market(symbol), reports.first()) else {
+        return;
+    };
+    let instrument = market.instrument();
+    let own_reports: Vec<&ExecutionReport> = reports.iter().filter(|r| r.order_id == first.order_id).collect();
+
+    for report in &own_reports {
+        match report.execution_type {
+            ExecutionType::PartialFill | ExecutionType::Fill => println!(
+                "🔥 TRADE EXECUTED: {} shares at ${}",
+                report.last_quantity,
+                instrument.format_price(report.last_price.unwrap_or_default())
+            ),
+            ExecutionType::Cancelled => println!(
+                "Unfilled: {} shares (no more liquidity, remainder cancelled)",
+                report.quantity - report.cumulative_quantity
+            ),
+            ExecutionType::Expired => println!("Order expired"),
+            ExecutionType::Rejected => println!("Rejected: {}", report.reason.as_deref().unwrap_or("unknown reason")),
+            ExecutionType::SelfTradePrevented => println!(
+                "Self-trade prevented: {} shares left working",
+                report.leaves_quantity
+            ),
+            ExecutionType::Queued => println!("Market halted: order queued until it reopens"),
+            ExecutionType::New | ExecutionType::Replaced => {}
+        }
+    }
+
+    if let Some(last) = own_reports.last() {
+        if let Some(average_price) = last.average_price {
+            println!(
+                "Filled {} of {} shares, average price ${:.*}",
+                last.cumulative_quantity,
+                last.quantity,
+                instrument.decimals() + 2,
+                average_price / instrument.price_scale as f64
+            );
+        }
+    }
+}
+
+fn place_market_order(exchange: &mut Exchange, symbol: &str, side: OrderSide, quantity: u64) {
+    match exchange.place_market_order(symbol, side, quantity) {
+        Ok(reports) => {
+            println!("{} market order placed: {} {} shares (ID: {})", side_name(side), symbol, quantity, reports[0].order_id);
+            print_reports(exchange, symbol, &reports);
+        }
+        Err(msg) => println!("Error: {}", msg),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use market_sim::clock::SimulatedClock;
+    use market_sim::error::MarketError;
+    use market_sim::execution::ExecutionType;
+    use market_sim::fees::{FeeRate, FeeSchedule};
+    use market_sim::halt::{CircuitBreaker, HaltPolicy, HaltReason, HaltedOrderHandling, Reopen};
+    use market_sim::history::{MemoryOrderArchive, RetentionPolicy};
+    use market_sim::ids::{SeededIds, SequentialIds};
+    use market_sim::journal::Journal;
+    use market_sim::market::MarketSimulator;
+    use market_sim::market_data::{L2Book, L2Message, L2Publisher, L3Book, L3Message, OrderEventKind};
+    use market_sim::matching::{Hybrid, ProRata, ProRataRounding};
+    use market_sim::observer::{EventLog, MarketEvent, TopOfBook};
+    use market_sim::risk::{RiskLimits, RiskRejection};
+    use market_sim::snapshot::SNAPSHOT_VERSION;
+    use market_sim::order_book::{Liquidity, Order, OrderBook, OrderStatus, SelfTradePrevention, TimeInForce};
+
+    fn market_open() -> chrono::DateTime<chrono::Utc> {
+        chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2024, 1, 2, 9, 30, 0).unwrap()
+    }
+
+    fn price(value: f64) -> Price {
+        Instrument::default().to_price(value).unwrap()
+    }
 
     #[test]
     fn test_basic_order_placement() {
         let mut market = MarketSimulator::new(1.0);
         
         // Place a buy order
-        let buy_order_id = market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+        let buy_order_id = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
         assert!(market.get_order(&buy_order_id).is_some());
         
         // Place a sell order with sufficient spread
-        let sell_order_id = market.place_order(OrderSide::Sell, 102.0, 5).unwrap();
+        let sell_order_id = market.place_order(OrderSide::Sell, 102.0, 5).unwrap()[0].order_id;
         assert!(market.get_order(&sell_order_id).is_some());
     }
 
@@ -200,20 +389,1492 @@ mod tests {
         let trades = market.get_all_trades();
         assert_eq!(trades.len(), 1);
         assert_eq!(trades[0].quantity, 5);
-        assert_eq!(trades[0].price.into_inner(), 100.0); // Trade executes at the sell order's price
+        assert_eq!(trades[0].price, price(100.0)); // Trade executes at the sell order's price
+    }
+
+    #[test]
+    fn test_market_order_sweeps_book() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 101.0, 5).unwrap();
+
+        let order_id = market.place_market_order(OrderSide::Buy, 12).unwrap()[0].order_id;
+
+        let trades = market.get_all_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].price, price(100.0));
+        assert_eq!(trades[1].price, price(101.0));
+
+        // The unfilled remainder is reported and never rests in the book
+        let order = market.get_order(&order_id).unwrap();
+        assert_eq!(order.filled_quantity, 10);
+        assert_eq!(order.remaining_quantity(), 2);
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert!(market.get_best_bid().is_none());
+        assert!(market.get_best_ask().is_none());
+    }
+
+    #[test]
+    fn test_market_order_skips_spread_check() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+        market.place_order(OrderSide::Sell, 102.0, 10).unwrap();
+
+        market.place_market_order(OrderSide::Sell, 4).unwrap();
+
+        let trades = market.get_all_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, price(100.0));
+    }
+
+    #[test]
+    fn test_immediate_or_cancel_does_not_rest() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+
+        let order = Order::new(OrderSide::Buy, price(100.0), 8).with_time_in_force(TimeInForce::ImmediateOrCancel);
+        let order_id = market.submit_order(order).unwrap()[0].order_id;
+
+        let order = market.get_order(&order_id).unwrap();
+        assert_eq!(order.filled_quantity, 5);
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert!(market.get_best_bid().is_none());
+    }
+
+    #[test]
+    fn test_fill_or_kill_leaves_book_untouched() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 102.0, 5).unwrap();
+
+        // Only 5 shares are available at or below 101
+        let order = Order::new(OrderSide::Buy, price(101.0), 8).with_time_in_force(TimeInForce::FillOrKill);
+        let order_id = market.submit_order(order).unwrap()[0].order_id;
+        assert!(market.get_all_trades().is_empty());
+        assert_eq!(market.get_order(&order_id).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(market.get_market_depth(5).1, vec![(price(100.0), 5), (price(102.0), 5)]);
+
+        let order = Order::new(OrderSide::Buy, price(102.0), 8).with_time_in_force(TimeInForce::FillOrKill);
+        let order_id = market.submit_order(order).unwrap()[0].order_id;
+        assert_eq!(market.get_all_trades().len(), 2);
+        assert_eq!(market.get_order(&order_id).unwrap().status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn test_day_and_good_till_date_expiry() {
+        // A fixed time of day, so the expiry never falls on the next day
+        let mut market = MarketSimulator::new(1.0);
+        market.set_clock(SimulatedClock::new(market_open()));
+
+        let expiry = market_open() + chrono::Duration::hours(1);
+        let gtd = Order::new(OrderSide::Buy, price(100.0), 10).with_time_in_force(TimeInForce::GoodTillDate(expiry));
+        let gtd_id = market.submit_order(gtd).unwrap()[0].order_id;
+        let day = Order::new(OrderSide::Buy, price(99.0), 10).with_time_in_force(TimeInForce::Day);
+        let day_id = market.submit_order(day).unwrap()[0].order_id;
+
+        let expired = market.expire_orders(expiry).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].order_id, gtd_id);
+        assert_eq!(expired[0].status, OrderStatus::Expired);
+
+        let expired = market.end_of_day().unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].order_id, day_id);
+        assert!(market.get_best_bid().is_none());
+    }
+
+    #[test]
+    fn test_stop_orders_are_hidden_until_triggered() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+        market.place_order(OrderSide::Sell, 103.0, 10).unwrap();
+
+        let stop_id = market.submit_order(Order::stop(OrderSide::Sell, price(99.0), 5)).unwrap()[0].order_id;
+        assert_eq!(market.get_market_depth(5).1, vec![(price(103.0), 10)]);
+        assert_eq!(market.get_order(&stop_id).unwrap().status, OrderStatus::Open);
+
+        // Trading at 100 does not reach the 99 stop
+        market.place_market_order(OrderSide::Sell, 4).unwrap();
+        assert_eq!(market.get_all_trades().len(), 1);
+        assert_eq!(market.get_order(&stop_id).unwrap().filled_quantity, 0);
+    }
+
+    #[test]
+    fn test_stop_cascade() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Buy, 100.0, 5).unwrap();
+        market.place_order(OrderSide::Buy, 98.0, 5).unwrap();
+        market.place_order(OrderSide::Buy, 95.0, 5).unwrap();
+
+        // The first stop fires on a trade at 100 and sells into 98, which fires the
+        // second stop, which sells into 95
+        let first = market.submit_order(Order::stop(OrderSide::Sell, price(100.0), 5)).unwrap()[0].order_id;
+        let second = market.submit_order(Order::stop_limit(OrderSide::Sell, price(98.0), price(90.0), 5)).unwrap()[0].order_id;
+
+        market.place_market_order(OrderSide::Sell, 5).unwrap();
+
+        let prices: Vec<Price> = market.get_all_trades().iter().map(|t| t.price).collect();
+        assert_eq!(prices, vec![price(100.0), price(98.0), price(95.0)]);
+        assert_eq!(market.get_order(&first).unwrap().status, OrderStatus::Filled);
+        assert_eq!(market.get_order(&second).unwrap().status, OrderStatus::Filled);
+        assert_eq!(market.get_last_trade_price(), Some(price(95.0)));
+    }
+
+    #[test]
+    fn test_triggered_stops_keep_their_time_in_force() {
+        let clock = SimulatedClock::new(market_open());
+        let mut market = MarketSimulator::new(1.0);
+        market.set_clock(clock.clone());
+        market.place_order(OrderSide::Sell, 101.0, 4).unwrap();
+
+        // Only 3 are left once the stop fires, so the fill-or-kill stop is killed
+        let fok = Order::stop(OrderSide::Buy, price(101.0), 10).with_time_in_force(TimeInForce::FillOrKill);
+        let fok = market.submit_order(fok).unwrap()[0].order_id;
+        let expiry = market_open() + chrono::Duration::minutes(1);
+        let gtd = Order::stop(OrderSide::Buy, price(101.0), 1).with_time_in_force(TimeInForce::GoodTillDate(expiry));
+        let gtd = market.submit_order(gtd).unwrap()[0].order_id;
+
+        clock.advance(chrono::Duration::minutes(2));
+        market.place_market_order(OrderSide::Buy, 1).unwrap();
+        assert_eq!(market.get_all_trades().len(), 1);
+        assert_eq!(market.get_order(&fok).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(market.get_order(&gtd).unwrap().status, OrderStatus::Expired);
+        assert_eq!(market.get_market_depth(1).1, vec![(price(101.0), 3)]);
+    }
+
+    #[test]
+    fn test_iceberg_shows_only_peak_and_refreshes_to_back_of_queue() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let iceberg_id = market.submit_order(Order::iceberg(OrderSide::Sell, price(100.0), 25, 10)).unwrap()[0].order_id;
+        let plain_id = market.place_order(OrderSide::Sell, 100.0, 5).unwrap()[0].order_id;
+        assert_eq!(market.get_market_depth(1).1, vec![(price(100.0), 15)]);
+
+        // Consuming the first slice refreshes it behind the plain order, so the
+        // next 5 shares trade against the plain order
+        market.place_market_order(OrderSide::Buy, 15).unwrap();
+        let trades = market.get_all_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].sell_order_id, trades[0].quantity), (iceberg_id, 10));
+        assert_eq!((trades[1].sell_order_id, trades[1].quantity), (plain_id, 5));
+
+        let iceberg = market.get_order(&iceberg_id).unwrap();
+        assert_eq!(iceberg.remaining_quantity(), 15);
+        assert_eq!(iceberg.visible_quantity(), 10);
+        assert_eq!(iceberg.reserve_quantity(), 5);
+        assert_eq!(market.get_market_depth(1).1, vec![(price(100.0), 10)]);
+    }
+
+    #[test]
+    fn test_amend_quantity_down_keeps_priority() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let first = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+        let second = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+
+        market.amend_order(first, 100.0, 6).unwrap();
+        assert_eq!(market.get_market_depth(1).0, vec![(price(100.0), 16)]);
+
+        market.place_market_order(OrderSide::Sell, 6).unwrap();
+        assert_eq!(market.get_all_trades()[0].buy_order_id, first);
+        assert_eq!(market.get_order(&first).unwrap().status, OrderStatus::Filled);
+        assert_eq!(market.get_order(&second).unwrap().filled_quantity, 0);
+    }
+
+    #[test]
+    fn test_amend_quantity_up_loses_priority() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let first = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+        let second = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+
+        market.amend_order(first, 100.0, 12).unwrap();
+        market.place_market_order(OrderSide::Sell, 5).unwrap();
+        assert_eq!(market.get_all_trades()[0].buy_order_id, second);
+        assert_eq!(market.get_order(&first).unwrap().quantity, 12);
+    }
+
+    #[test]
+    fn test_amend_price_crossing_matches_and_is_spread_checked() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let buy = market.place_order(OrderSide::Buy, 98.0, 10).unwrap()[0].order_id;
+        market.place_order(OrderSide::Sell, 101.0, 4).unwrap();
+
+        // 100.5 would leave a spread under 1%
+        assert!(market.amend_order(buy, 100.5, 10).is_err());
+        assert_eq!(market.get_order(&buy).unwrap().price, price(98.0));
+
+        market.amend_order(buy, 101.0, 10).unwrap();
+        let trades = market.get_all_trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].buy_order_id, trades[0].quantity), (buy, 4));
+        assert_eq!(market.get_market_depth(1).0, vec![(price(101.0), 6)]);
+    }
+
+    #[test]
+    fn test_prices_off_the_tick_grid_are_rejected() {
+        let mut market = MarketSimulator::with_instrument(1.0, Instrument::new(100, 5));
+
+        assert!(market.place_order(OrderSide::Buy, 100.03, 10).is_err());
+        assert!(market.place_order(OrderSide::Buy, 100.001, 10).is_err());
+        assert!(market.submit_order(Order::new(OrderSide::Buy, 10_003, 10)).is_err());
+        market.place_order(OrderSide::Buy, 100.05, 10).unwrap();
+        assert_eq!(market.get_market_depth(1).0, vec![(10_005, 10)]);
+        assert_eq!(market.place_order(OrderSide::Buy, 1e17, 10), Err(MarketError::InvalidPrice(1e17)));
+    }
+
+    #[test]
+    fn test_price_accessors_keep_one_unit_each() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Buy, 100.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 102.5, 5).unwrap();
+
+        assert_eq!((market.get_best_bid(), market.get_best_ask()), (Some(price(100.0)), Some(price(102.5))));
+        assert_eq!(market.get_spread(), Some(price(2.5)));
+        assert_eq!((market.get_best_bid_f64(), market.get_best_ask_f64()), (Some(100.0), Some(102.5)));
+        assert_eq!(market.get_spread_f64(), Some(2.5));
+
+        market.place_market_order(OrderSide::Buy, 1).unwrap();
+        assert_eq!(market.get_last_trade_price(), Some(price(102.5)));
+        assert_eq!(market.get_last_trade_price_f64(), Some(102.5));
+    }
+
+    #[test]
+    fn test_float_rounding_does_not_split_price_levels() {
+        let mut market = MarketSimulator::new(1.0);
+
+        market.place_order(OrderSide::Sell, 100.3, 5).unwrap();
+        market.place_order(OrderSide::Sell, 100.1 + 0.2, 5).unwrap();
+        assert_eq!(market.get_market_depth(5).1, vec![(price(100.3), 10)]);
+        assert_eq!(market.instrument().format_price(price(100.3)), "100.30");
+    }
+
+    #[test]
+    fn test_exchange_routes_orders_by_symbol() {
+        let mut exchange = Exchange::new();
+        exchange.add_instrument("AAPL", Instrument::default(), 1.0).unwrap();
+        exchange.add_instrument("msft", Instrument::new(100, 5), 1.0).unwrap();
+        assert!(exchange.add_instrument("MSFT", Instrument::default(), 1.0).is_err());
+        assert_eq!(exchange.symbols(), vec!["AAPL", "MSFT"]);
+
+        exchange.place_order("AAPL", OrderSide::Sell, 100.0, 10).unwrap();
+        exchange.place_order("MSFT", OrderSide::Sell, 300.05, 10).unwrap();
+        // Each symbol keeps its own tick size
+        assert!(exchange.place_order("MSFT", OrderSide::Buy, 290.01, 10).is_err());
+
+        exchange.place_market_order("aapl", OrderSide::Buy, 4).unwrap();
+        assert_eq!(exchange.market("AAPL").unwrap().get_all_trades().len(), 1);
+        assert!(exchange.market("MSFT").unwrap().get_all_trades().is_empty());
+        assert_eq!(exchange.get_market_depth("AAPL", 1).unwrap().1, vec![(price(100.0), 6)]);
+        assert_eq!(exchange.get_market_depth("MSFT", 1).unwrap().1, vec![(price(300.05), 10)]);
+
+        assert!(exchange.place_order("GOOG", OrderSide::Buy, 100.0, 1).is_err());
+        assert!(exchange.get_market_depth("GOOG", 1).is_none());
+    }
+
+    #[test]
+    fn test_accounts_track_cash_positions_and_pnl() {
+        let mut market = MarketSimulator::new(1.0);
+        market.deposit(1, 10_000.0).unwrap();
+        market.deposit(2, 10_000.0).unwrap();
+
+        market.submit_order(Order::new(OrderSide::Sell, price(100.0), 10).with_account(2)).unwrap();
+        market.submit_order(Order::market(OrderSide::Buy, 10).with_account(1)).unwrap();
+
+        let trade = &market.get_all_trades()[0];
+        assert_eq!((trade.buy_account_id, trade.sell_account_id), (1, 2));
+
+        let buyer = market.get_account(1).unwrap();
+        assert_eq!(buyer.position, 10);
+        assert_eq!(buyer.cash, price(9_000.0));
+        let seller = market.get_account(2).unwrap();
+        assert_eq!(seller.position, -10);
+        assert_eq!(seller.cash, price(11_000.0));
+
+        // Buyer sells 4 back at 110 to a new participant: 40 realised, 60 unrealised at 110
+        market.submit_order(Order::new(OrderSide::Buy, price(110.0), 4).with_account(3)).unwrap();
+        market.submit_order(Order::market(OrderSide::Sell, 4).with_account(1)).unwrap();
+
+        let buyer = market.get_account(1).unwrap();
+        assert_eq!(buyer.position, 6);
+        assert_eq!(buyer.realized_pnl, price(40.0));
+        assert_eq!(market.get_unrealized_pnl(1), Some(price(60.0)));
+        assert_eq!(market.get_unrealized_pnl(2), Some(-price(100.0)));
+        assert_eq!(market.get_account(3).unwrap().cash, -price(440.0));
+    }
+
+    #[test]
+    fn test_huge_balances_saturate_instead_of_overflowing() {
+        let mut market = MarketSimulator::new(1.0);
+        market.deposit(1, 1e30).unwrap();
+        market.deposit(1, 1.0).unwrap();
+        let account = market.get_account(1).unwrap();
+        assert_eq!(account.cash, i64::MAX);
+
+        let mut short = account.clone();
+        short.position = -10;
+        assert_eq!(short.market_value(i64::MAX), i64::MAX + i64::MIN);
+        assert_eq!(short.unrealized_pnl(i64::MAX), i64::MIN);
+    }
+
+    #[test]
+    fn test_risk_checks_reject_with_structured_reasons() {
+        let mut market = MarketSimulator::new(1.0);
+        market.add_risk_check(RiskLimits {
+            check_buying_power: true,
+            max_short_position: Some(0),
+            max_order_quantity: Some(1_000),
+            price_band_percentage: Some(10.0),
+            ..RiskLimits::default()
+        });
+        market.deposit(1, 1_500.0).unwrap();
+
+        // Buying power counts cash already committed to resting buys
+        market.submit_order(Order::new(OrderSide::Buy, price(100.0), 10).with_account(1)).unwrap();
+        let result = market.submit_order(Order::new(OrderSide::Buy, price(100.0), 6).with_account(1));
+        assert_eq!(
+            result.unwrap_err(),
+            MarketError::Risk(RiskRejection::InsufficientBuyingPower {
+                required: price(600.0),
+                available: price(500.0),
+            })
+        );
+
+        let result = market.submit_order(Order::new(OrderSide::Sell, price(102.0), 1).with_account(1));
+        assert!(matches!(result, Err(MarketError::Risk(RiskRejection::ShortLimitExceeded { .. }))));
+
+        let result = market.submit_order(Order::new(OrderSide::Buy, price(99.0), 2_000).with_account(2));
+        assert!(matches!(result, Err(MarketError::Risk(RiskRejection::OrderQuantityTooLarge { .. }))));
+    }
+
+    #[test]
+    fn test_orders_too_large_to_settle_are_rejected() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Sell, 100.0, 10).unwrap();
+        assert_eq!(
+            market.place_order(OrderSide::Buy, 1e12, 999_999_999),
+            Err(MarketError::NotionalOverflow { price: price(1e12), quantity: 999_999_999 })
+        );
+
+        // Each level fits, and a market order sweeping both adds up beyond i64
+        for ask in [1e10, 1.1e10] {
+            market.submit_order(Order::new(OrderSide::Sell, price(ask), 5_000_000).with_account(1)).unwrap();
+        }
+        let reports = market.place_market_order(OrderSide::Buy, 10_000_010).unwrap();
+        assert_eq!(market.get_all_trades().len(), 3);
+        let average_price = market.get_order(&reports[0].order_id).unwrap().average_price().unwrap();
+        assert!(average_price > price(1e10) as f64 && average_price < price(1.1e10) as f64);
+        assert_eq!(market.get_account(1).unwrap().position, -10_000_000);
+    }
+
+    #[test]
+    fn test_fat_finger_notional_is_rejected_not_overflowed() {
+        let mut market = MarketSimulator::new(1.0);
+        market.add_risk_check(RiskLimits {
+            check_buying_power: true,
+            max_order_notional: Some(price(1_000_000.0)),
+            ..RiskLimits::default()
+        });
+        market.deposit(0, 1_000.0).unwrap();
+        market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+
+        let result = market.place_order(OrderSide::Buy, 1e12, 999_999_999);
+        assert_eq!(
+            result.unwrap_err(),
+            MarketError::Risk(RiskRejection::OrderNotionalTooLarge {
+                notional: i64::MAX,
+                limit: price(1_000_000.0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_oversized_orders_are_over_position_limits_not_wrapped() {
+        let mut market = MarketSimulator::new(1.0);
+        market.add_risk_check(RiskLimits {
+            max_long_position: Some(10),
+            max_short_position: Some(10),
+            ..RiskLimits::default()
+        });
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+
+        let result = market.place_market_order(OrderSide::Sell, u64::MAX);
+        assert!(matches!(result, Err(MarketError::Risk(RiskRejection::ShortLimitExceeded { .. }))));
+        let result = market.place_market_order(OrderSide::Buy, u64::MAX);
+        assert_eq!(
+            result.unwrap_err(),
+            MarketError::Risk(RiskRejection::PositionLimitExceeded { projected_position: i64::MAX, limit: 10 })
+        );
+        assert!(market.get_all_trades().is_empty());
+    }
+
+    #[test]
+    fn test_price_band_around_last_trade() {
+        let mut market = MarketSimulator::new(1.0);
+        market.add_risk_check(RiskLimits {
+            price_band_percentage: Some(10.0),
+            ..RiskLimits::default()
+        });
+
+        market.place_order(OrderSide::Buy, 100.0, 5).unwrap();
+        market.place_market_order(OrderSide::Sell, 5).unwrap();
+
+        let result = market.place_order(OrderSide::Sell, 120.0, 5);
+        assert_eq!(
+            result.unwrap_err(),
+            MarketError::Risk(RiskRejection::PriceOutsideBand {
+                price: price(120.0),
+                reference_price: price(100.0),
+                band_percentage: 10.0,
+            })
+        );
+        market.place_order(OrderSide::Sell, 105.0, 5).unwrap();
+    }
+
+    #[test]
+    fn test_rejections_are_structured_errors() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+
+        match market.place_order(OrderSide::Sell, 100.5, 5) {
+            Err(MarketError::SpreadViolation { side, spread_percentage, minimum_spread_percentage }) => {
+                assert_eq!(side, OrderSide::Sell);
+                assert!((spread_percentage - 0.4987).abs() < 1e-3);
+                assert_eq!(minimum_spread_percentage, 1.0);
+            }
+            other => panic!("expected a spread violation, got {:?}", other),
+        }
+        assert_eq!(market.place_order(OrderSide::Buy, 99.0, 0), Err(MarketError::ZeroQuantity));
+        assert_eq!(market.place_order(OrderSide::Buy, -1.0, 5), Err(MarketError::InvalidPrice(-1.0)));
+        assert!(matches!(market.place_order(OrderSide::Buy, 99.001, 5), Err(MarketError::PriceTooPrecise { .. })));
+
+        let err = market.place_order(OrderSide::Sell, 100.5, 5).unwrap_err();
+        assert_eq!(err.to_string(), "Sell order would create spread of 0.50%, minimum required is 1.00%");
+        let _: &dyn std::error::Error = &err;
+    }
+
+    #[test]
+    fn test_cancel_distinguishes_unknown_and_filled_orders() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let sell_id = market.place_order(OrderSide::Sell, 100.0, 5).unwrap()[0].order_id;
+        let buy_id = market.place_order(OrderSide::Buy, 100.0, 5).unwrap()[0].order_id;
+        assert_eq!(market.cancel_order(sell_id).unwrap_err(), MarketError::OrderAlreadyFilled(sell_id));
+        assert_eq!(market.cancel_order(buy_id).unwrap_err(), MarketError::OrderAlreadyFilled(buy_id));
+
+        let unknown = uuid::Uuid::new_v4();
+        assert_eq!(market.cancel_order(unknown).unwrap_err(), MarketError::UnknownOrder(unknown));
+
+        // A market order's unfilled remainder is already cancelled
+        let market_id = market.place_market_order(OrderSide::Buy, 5).unwrap()[0].order_id;
+        assert_eq!(
+            market.cancel_order(market_id).unwrap_err(),
+            MarketError::OrderClosed { order_id: market_id, status: OrderStatus::Cancelled }
+        );
     }
 
     #[test]
     fn test_order_cancellation() {
         let mut market = MarketSimulator::new(1.0);
         
-        let order_id = market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+        let order_id = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
         
         let cancelled_order = market.cancel_order(order_id);
-        assert!(cancelled_order.is_some());
+        assert!(cancelled_order.is_ok());
         
         // Order should no longer be in the book
         let order = market.get_order(&order_id);
         assert!(order.is_none());
     }
+
+    #[test]
+    fn test_sweep_reports_every_fill_with_average_price() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let first = market.place_order(OrderSide::Sell, 100.0, 5).unwrap()[0].order_id;
+        let second = market.place_order(OrderSide::Sell, 101.0, 5).unwrap()[0].order_id;
+
+        let reports = market.place_market_order(OrderSide::Buy, 12).unwrap();
+        let order_id = reports[0].order_id;
+        let kinds: Vec<_> = reports.iter().map(|r| (r.order_id, r.execution_type)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (order_id, ExecutionType::New),
+                (order_id, ExecutionType::PartialFill),
+                (first, ExecutionType::Fill),
+                (order_id, ExecutionType::PartialFill),
+                (second, ExecutionType::Fill),
+                (order_id, ExecutionType::Cancelled),
+            ]
+        );
+
+        let second_fill = &reports[3];
+        assert_eq!(second_fill.last_price, Some(price(101.0)));
+        assert_eq!(second_fill.last_quantity, 5);
+        assert_eq!(second_fill.cumulative_quantity, 10);
+        assert_eq!(second_fill.leaves_quantity, 2);
+        assert_eq!(second_fill.average_price, Some(price(100.5) as f64));
+
+        // The cancelled remainder leaves nothing working
+        let done = reports.last().unwrap();
+        assert_eq!((done.status, done.cumulative_quantity, done.leaves_quantity), (OrderStatus::Cancelled, 10, 0));
+    }
+
+    #[test]
+    fn test_book_rejects_and_expires_with_reports() {
+        let mut book = OrderBook::new();
+
+        let rejected = book.add_order(Order::new(OrderSide::Buy, price(100.0), 0));
+        assert!(rejected.trades.is_empty());
+        assert_eq!(rejected.reports.len(), 1);
+        assert_eq!(rejected.reports[0].execution_type, ExecutionType::Rejected);
+        assert_eq!(rejected.reports[0].status, OrderStatus::Rejected);
+        assert!(rejected.reports[0].reason.is_some());
+
+        let stale = Order::new(OrderSide::Buy, price(100.0), 5)
+            .with_time_in_force(TimeInForce::GoodTillDate(chrono::Utc::now() - chrono::Duration::hours(1)));
+        let expired = book.add_order(stale);
+        assert_eq!(expired.reports[0].execution_type, ExecutionType::Expired);
+        assert_eq!(expired.reports[0].leaves_quantity, 0);
+
+        let mut market = MarketSimulator::new(1.0);
+        let resting = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+        let cancelled = market.cancel_order(resting).unwrap();
+        assert_eq!((cancelled.execution_type, cancelled.leaves_quantity), (ExecutionType::Cancelled, 0));
+    }
+
+    #[test]
+    fn test_trades_record_aggressor_and_liquidity() {
+        let mut market = MarketSimulator::new(1.0);
+
+        let resting_sell = market.place_order(OrderSide::Sell, 100.0, 10).unwrap()[0].order_id;
+        let reports = market.place_order(OrderSide::Buy, 100.0, 4).unwrap();
+        market.place_order(OrderSide::Buy, 98.0, 10).unwrap();
+        market.place_market_order(OrderSide::Sell, 7).unwrap();
+
+        let trades = market.get_all_trades();
+        assert_eq!(trades[0].sell_order_id, resting_sell);
+        assert_eq!(trades[0].aggressor_side, Some(OrderSide::Buy));
+        assert_eq!((trades[0].buy_liquidity, trades[0].sell_liquidity), (Liquidity::Taker, Liquidity::Maker));
+        assert_eq!(trades[1].aggressor_side, Some(OrderSide::Sell));
+        assert_eq!((trades[1].buy_liquidity, trades[1].sell_liquidity), (Liquidity::Maker, Liquidity::Taker));
+
+        // Fill reports carry the leg's own liquidity
+        let fills: Vec<_> = reports.iter().filter(|r| r.is_fill()).map(|r| (r.side, r.liquidity)).collect();
+        assert_eq!(fills, vec![(OrderSide::Buy, Some(Liquidity::Taker)), (OrderSide::Sell, Some(Liquidity::Maker))]);
+
+        assert_eq!(market.get_signed_volume(), 4 - 7);
+        assert_eq!(market.get_recent_signed_volume(1), -7);
+        assert_eq!(market.get_order_flow_imbalance(2), Some(-3.0 / 11.0));
+        assert_eq!(MarketSimulator::new(1.0).get_order_flow_imbalance(10), None);
+    }
+
+    #[test]
+    fn test_maker_taker_fees_with_volume_tiers() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_fee_schedule(
+            FeeSchedule::new(FeeRate::BasisPoints(-2.0), FeeRate::PerShare(1.0))
+                .with_minimum_fee(5)
+                .with_tier(100, FeeRate::BasisPoints(-3.0), FeeRate::PerShare(0.5)),
+        );
+
+        let mut trade = |quantity| {
+            market.submit_order(Order::new(OrderSide::Sell, price(100.0), quantity).with_account(1)).unwrap();
+            market.submit_order(Order::new(OrderSide::Buy, price(100.0), quantity).with_account(2)).unwrap();
+        };
+        trade(10);
+        trade(200);
+        // The taker is now past 100 shares this month: half a unit per share, but
+        // never less than the minimum fee
+        trade(2);
+
+        let fees: Vec<_> = market.get_all_trades().iter().map(|t| (t.sell_fee, t.buy_fee)).collect();
+        assert_eq!(fees, vec![(-20, 10), (-400, 200), (-6, 5)]);
+
+        let maker = market.get_account(1).unwrap();
+        let taker = market.get_account(2).unwrap();
+        assert_eq!(maker.fees_paid, -426);
+        assert_eq!(maker.cash, 212 * price(100.0) + 426);
+        assert_eq!(taker.fees_paid, 215);
+        assert_eq!(taker.cash, -212 * price(100.0) - 215);
+        assert_eq!(market.get_fees_collected(), 215 - 426);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_modes() {
+        let mut market = MarketSimulator::new(1.0);
+        let sell = |account, quantity| Order::new(OrderSide::Sell, price(100.0), quantity).with_account(account);
+        let buy = |account, quantity, mode| {
+            Order::new(OrderSide::Buy, price(100.0), quantity).with_account(account).with_self_trade_prevention(mode)
+        };
+
+        let own = market.submit_order(sell(1, 10)).unwrap()[0].order_id;
+        market.submit_order(sell(2, 5)).unwrap();
+
+        // Cancel newest leaves the resting order alone
+        let reports = market.submit_order(buy(1, 4, SelfTradePrevention::CancelNewest)).unwrap();
+        assert_eq!(reports[1].execution_type, ExecutionType::SelfTradePrevented);
+        assert_eq!(reports[1].status, OrderStatus::Cancelled);
+        assert_eq!(reports.len(), 2);
+        assert!(market.get_all_trades().is_empty());
+
+        // Decrement-and-cancel takes 4 off both: the incoming order is used up
+        let reports = market.submit_order(buy(1, 4, SelfTradePrevention::DecrementAndCancel)).unwrap();
+        let prevented: Vec<_> = reports[1..].iter().map(|r| (r.order_id == own, r.status, r.leaves_quantity)).collect();
+        assert_eq!(prevented, vec![(false, OrderStatus::Cancelled, 0), (true, OrderStatus::Open, 6)]);
+        assert_eq!(market.get_market_depth(1).1, vec![(price(100.0), 11)]);
+
+        // Cancel oldest removes the own order and keeps matching behind it
+        let reports = market.submit_order(buy(1, 8, SelfTradePrevention::CancelOldest)).unwrap();
+        assert_eq!((reports[1].order_id, reports[1].status), (own, OrderStatus::Cancelled));
+        assert!(market.get_order(&own).is_none());
+        let trades = market.get_all_trades();
+        assert_eq!((trades.len(), trades[0].sell_account_id, trades[0].quantity), (1, 2, 5));
+        assert_eq!(market.get_best_bid(), Some(price(100.0)));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_by_account_and_group() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_self_trade_prevention(4, Some(SelfTradePrevention::CancelBoth));
+
+        let resting = market
+            .submit_order(Order::new(OrderSide::Sell, price(101.0), 5).with_account(3).with_self_trade_group(7))
+            .unwrap()[0]
+            .order_id;
+        let reports = market
+            .submit_order(Order::market(OrderSide::Buy, 5).with_account(4).with_self_trade_group(7))
+            .unwrap();
+
+        let prevented: Vec<_> = reports
+            .iter()
+            .filter(|r| r.execution_type == ExecutionType::SelfTradePrevented)
+            .map(|r| r.status)
+            .collect();
+        assert_eq!(prevented, vec![OrderStatus::Cancelled, OrderStatus::Cancelled]);
+        assert!(reports[1].reason.as_ref().unwrap().contains(&resting.to_string()));
+        assert!(market.get_all_trades().is_empty());
+        assert!(market.get_best_ask().is_none());
+
+        // Outside the group the same two accounts trade normally
+        market.submit_order(Order::new(OrderSide::Sell, price(101.0), 5).with_account(3)).unwrap();
+        market.submit_order(Order::market(OrderSide::Buy, 5).with_account(4)).unwrap();
+        assert_eq!(market.get_all_trades().len(), 1);
+    }
+
+    #[test]
+    fn test_call_auction_collects_and_uncrosses() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_phase(TradingPhase::Closed).unwrap();
+        assert_eq!(market.place_order(OrderSide::Buy, 100.0, 5), Err(MarketError::NotAllowedInPhase(TradingPhase::Closed)));
+        assert!(matches!(
+            market.set_phase(TradingPhase::ClosingAuction),
+            Err(MarketError::InvalidPhaseTransition { .. })
+        ));
+
+        market.set_phase(TradingPhase::PreOpenAuction).unwrap();
+        assert_eq!(
+            market.place_market_order(OrderSide::Buy, 5),
+            Err(MarketError::NotAllowedInPhase(TradingPhase::PreOpenAuction))
+        );
+
+        // Crossing orders rest without trading and without a spread check
+        market.place_order(OrderSide::Sell, 99.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 102.0, 8).unwrap();
+        market.place_order(OrderSide::Sell, 103.0, 12).unwrap();
+        market.place_order(OrderSide::Buy, 103.0, 20).unwrap();
+        market.place_order(OrderSide::Buy, 100.0, 10).unwrap();
+        assert!(market.get_all_trades().is_empty());
+
+        let indicative = market.get_indicative_uncross().unwrap();
+        assert_eq!((indicative.price, indicative.volume, indicative.imbalance), (price(103.0), 20, -5));
+
+        let reports = market.set_phase(TradingPhase::Continuous).unwrap();
+        assert_eq!(reports.iter().filter(|r| r.is_fill()).count(), 6);
+        let trades = market.get_all_trades();
+        assert!(trades.iter().all(|t| t.price == price(103.0) && t.aggressor_side.is_none()));
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 20);
+        assert_eq!(trades[0].buy_liquidity, Liquidity::Auction);
+        assert_eq!(market.get_best_bid(), Some(price(100.0)));
+        assert_eq!(market.get_market_depth(1).1, vec![(price(103.0), 5)]);
+        assert_eq!(market.get_indicative_uncross(), None);
+    }
+
+    #[test]
+    fn test_uncross_price_tie_breaks() {
+        let book_with = |bids: &[(f64, u64)], asks: &[(f64, u64)]| {
+            let mut book = OrderBook::new();
+            book.begin_auction();
+            for &(p, q) in bids {
+                book.add_order(Order::new(OrderSide::Buy, price(p), q));
+            }
+            for &(p, q) in asks {
+                book.add_order(Order::new(OrderSide::Sell, price(p), q));
+            }
+            book
+        };
+
+        // Equal volume and imbalance at 99 and 101: the reference price decides
+        let balanced = book_with(&[(101.0, 10)], &[(99.0, 10)]);
+        assert_eq!(balanced.indicative_uncross(Some(price(102.0))).unwrap().price, price(101.0));
+        assert_eq!(balanced.indicative_uncross(Some(price(98.0))).unwrap().price, price(99.0));
+
+        // Buyers left over at every candidate push the price up, sellers push it down
+        let buy_pressure = book_with(&[(101.0, 15)], &[(99.0, 10)]);
+        assert_eq!(buy_pressure.indicative_uncross(Some(price(98.0))).unwrap().price, price(101.0));
+        let sell_pressure = book_with(&[(101.0, 10)], &[(99.0, 15)]);
+        assert_eq!(sell_pressure.indicative_uncross(Some(price(102.0))).unwrap().price, price(99.0));
+
+        // Volume beats imbalance
+        let mut book = book_with(&[(101.0, 10), (100.0, 10)], &[(99.0, 10), (100.0, 8)]);
+        let uncross = book.indicative_uncross(None).unwrap();
+        assert_eq!((uncross.price, uncross.volume, uncross.imbalance), (price(100.0), 18, 2));
+        assert_eq!(book.uncross(None, chrono::Utc::now()).trades.len(), 2);
+        assert_eq!(book.get_market_depth(1), (vec![(price(100.0), 2)], vec![]));
+        assert!(!book.is_in_auction());
+        assert!(book.indicative_uncross(None).is_none());
+    }
+
+    #[test]
+    fn test_circuit_breaker_halts_queues_and_reopens_through_auction() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_halt_policy(HaltPolicy {
+            order_handling: HaltedOrderHandling::Queue,
+            halt_duration: chrono::Duration::minutes(5),
+            reopen: Reopen::Auction(chrono::Duration::minutes(1)),
+            circuit_breaker: Some(CircuitBreaker { move_percentage: 5.0, window: chrono::Duration::minutes(1) }),
+        });
+
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+        market.place_market_order(OrderSide::Buy, 5).unwrap();
+        market.place_order(OrderSide::Sell, 110.0, 5).unwrap();
+        market.place_market_order(OrderSide::Buy, 5).unwrap();
+
+        assert_eq!(market.phase(), TradingPhase::Halted);
+        let halt = *market.get_halt().unwrap();
+        assert_eq!(halt.reason, HaltReason::CircuitBreaker { trade_price: price(110.0), reference_price: price(100.0) });
+
+        // New orders wait in the queue and can still be cancelled
+        let bid = market.place_order(OrderSide::Buy, 105.0, 3).unwrap();
+        assert_eq!(bid[0].execution_type, ExecutionType::Queued);
+        let ask = market.place_order(OrderSide::Sell, 104.0, 2).unwrap()[0].order_id;
+        let dropped = market.place_order(OrderSide::Sell, 120.0, 1).unwrap()[0].order_id;
+        assert_eq!(market.cancel_order(dropped).unwrap().execution_type, ExecutionType::Cancelled);
+        assert_eq!(market.get_queued_orders().len(), 2);
+
+        assert!(market.update(halt.resumes_at - chrono::Duration::seconds(1)).unwrap().is_empty());
+        market.update(halt.resumes_at).unwrap();
+        assert_eq!(market.phase(), TradingPhase::PreOpenAuction);
+        assert!(market.get_order(&ask).is_some());
+        assert_eq!(market.get_indicative_uncross().unwrap().volume, 2);
+
+        let reports = market.update(halt.resumes_at + chrono::Duration::minutes(1)).unwrap();
+        assert_eq!(market.phase(), TradingPhase::Continuous);
+        assert_eq!(reports.iter().filter(|r| r.is_fill()).count(), 2);
+        assert_eq!(market.get_last_trade_price(), Some(price(105.0)));
+    }
+
+    fn breaker_policy(order_handling: HaltedOrderHandling) -> HaltPolicy {
+        HaltPolicy {
+            order_handling,
+            halt_duration: chrono::Duration::minutes(5),
+            reopen: Reopen::Continuous,
+            circuit_breaker: Some(CircuitBreaker { move_percentage: 5.0, window: chrono::Duration::minutes(1) }),
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_stops_matching_at_the_breaching_trade() {
+        for (order_handling, execution_type) in
+            [(HaltedOrderHandling::Reject, ExecutionType::Cancelled), (HaltedOrderHandling::Queue, ExecutionType::Queued)]
+        {
+            let mut market = MarketSimulator::new(1.0);
+            market.set_halt_policy(breaker_policy(order_handling));
+            for ask in [100.0, 110.0, 120.0] {
+                market.place_order(OrderSide::Sell, ask, 5).unwrap();
+            }
+
+            let reports = market.place_order(OrderSide::Buy, 120.0, 15).unwrap();
+            let prices: Vec<_> = market.get_all_trades().iter().map(|trade| trade.price).collect();
+            assert_eq!(prices, [price(100.0), price(110.0)]);
+            assert_eq!(market.phase(), TradingPhase::Halted);
+            assert_eq!(market.get_best_ask(), Some(price(120.0)));
+
+            let remainder = reports.last().unwrap();
+            assert_eq!((remainder.execution_type, remainder.cumulative_quantity), (execution_type, 10));
+            let queued = market.get_queued_orders().len();
+            assert_eq!(queued, usize::from(order_handling == HaltedOrderHandling::Queue));
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_never_queues_orders_that_cannot_rest() {
+        let immediate = [
+            Order::market(OrderSide::Buy, 15),
+            Order::new(OrderSide::Buy, price(121.0), 15).with_time_in_force(TimeInForce::ImmediateOrCancel),
+            Order::new(OrderSide::Buy, price(121.0), 10).with_time_in_force(TimeInForce::FillOrKill),
+        ];
+        for (order, trades) in immediate.into_iter().zip([2, 2, 0]) {
+            let mut market = MarketSimulator::new(1.0);
+            market.set_halt_policy(breaker_policy(HaltedOrderHandling::Queue));
+            for ask in [100.0, 110.0, 120.0] {
+                market.place_order(OrderSide::Sell, ask, 5).unwrap();
+            }
+
+            // A fill-or-kill order that could only complete past the breaker's band
+            // is killed before it trades
+            let reports = market.submit_order(order).unwrap();
+            assert_eq!(reports.last().unwrap().execution_type, ExecutionType::Cancelled);
+            assert_eq!(market.get_all_trades().len(), trades);
+            assert!(market.get_queued_orders().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_halt_ends_on_the_next_request_once_its_time_is_up() {
+        let clock = SimulatedClock::new(market_open());
+        let mut market = MarketSimulator::new(1.0);
+        market.set_clock(clock.clone());
+        market.set_halt_policy(breaker_policy(HaltedOrderHandling::Reject));
+        market.place_order(OrderSide::Sell, 100.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 110.0, 5).unwrap();
+        market.place_market_order(OrderSide::Buy, 10).unwrap();
+        assert_eq!(market.phase(), TradingPhase::Halted);
+
+        clock.advance(chrono::Duration::minutes(5));
+        assert!(market.place_order(OrderSide::Buy, 105.0, 5).is_ok());
+        assert_eq!(market.phase(), TradingPhase::Continuous);
+        assert!(market.get_halt().is_none());
+    }
+
+    #[test]
+    fn test_manual_halt_rejects_orders_but_allows_cancels() {
+        let mut market = MarketSimulator::new(1.0);
+        let resting = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+        let other = market.place_order(OrderSide::Buy, 99.0, 10).unwrap()[0].order_id;
+
+        market.halt().unwrap();
+        assert_eq!(market.get_halt().unwrap().reason, HaltReason::Manual);
+        assert_eq!(market.place_order(OrderSide::Sell, 100.0, 5), Err(MarketError::NotAllowedInPhase(TradingPhase::Halted)));
+        assert_eq!(market.amend_order(other, 98.0, 10), Err(MarketError::NotAllowedInPhase(TradingPhase::Halted)));
+        assert!(market.cancel_order(resting).is_ok());
+
+        market.resume().unwrap();
+        assert_eq!(market.phase(), TradingPhase::Continuous);
+        assert!(market.get_halt().is_none());
+        market.place_order(OrderSide::Sell, 99.0, 5).unwrap();
+        assert_eq!(market.get_all_trades().len(), 1);
+        assert!(market.resume().is_err());
+    }
+
+    #[test]
+    fn test_pro_rata_matching_with_minimum_allocation_and_rounding() {
+        for (rounding, expected) in [(ProRataRounding::Down, [18, 7, 0]), (ProRataRounding::Nearest, [17, 8, 0])] {
+            let mut market = MarketSimulator::new(1.0);
+            market.set_matching_policy(ProRata { minimum_allocation: 3, rounding });
+            let bids: Vec<_> = [60, 30, 10]
+                .into_iter()
+                .map(|quantity| market.place_order(OrderSide::Buy, 100.0, quantity).unwrap()[0].order_id)
+                .collect();
+
+            // Shares of 25 are 15, 7.5 and 2.5; the last is under the minimum and
+            // what is left over goes to the first order in time
+            market.place_order(OrderSide::Sell, 100.0, 25).unwrap();
+            let filled: Vec<_> = bids.iter().map(|id| market.get_order(id).unwrap().filled_quantity).collect();
+            assert_eq!(filled, expected);
+            assert_eq!(market.get_all_trades().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_hybrid_matching_fills_priority_order_first() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_matching_policy(Hybrid::default());
+        let first = market.place_order(OrderSide::Buy, 100.0, 10).unwrap()[0].order_id;
+        let second = market.place_order(OrderSide::Buy, 100.0, 40).unwrap()[0].order_id;
+        let third = market.place_order(OrderSide::Buy, 100.0, 40).unwrap()[0].order_id;
+
+        market.place_order(OrderSide::Sell, 100.0, 30).unwrap();
+        assert_eq!(market.get_order(&first).unwrap().status, OrderStatus::Filled);
+        assert_eq!(market.get_order(&second).unwrap().filled_quantity, 10);
+        assert_eq!(market.get_order(&third).unwrap().filled_quantity, 10);
+    }
+
+    #[test]
+    fn test_order_lookup_follows_the_book_through_fills_and_cancels() {
+        let mut market = MarketSimulator::new(1.0);
+        let iceberg = market
+            .submit_order(Order::iceberg(OrderSide::Sell, price(101.0), 30, 10))
+            .unwrap()[0]
+            .order_id;
+        let cancelled = market.place_order(OrderSide::Buy, 99.0, 5).unwrap()[0].order_id;
+        market.cancel_order(cancelled).unwrap();
+        // Takes the slot the cancelled order left behind
+        let bid = market.place_order(OrderSide::Buy, 98.0, 7).unwrap()[0].order_id;
+
+        market.place_order(OrderSide::Buy, 101.0, 12).unwrap();
+        let order = market.get_order(&iceberg).unwrap();
+        assert_eq!((order.filled_quantity, order.visible_quantity()), (12, 8));
+        assert_eq!(market.get_market_depth(1).1, vec![(price(101.0), 8)]);
+        assert!(market.get_order(&cancelled).is_none());
+        assert_eq!(market.get_order(&bid).unwrap().quantity, 7);
+        assert_eq!(market.get_market_depth(1).0, vec![(price(98.0), 7)]);
+    }
+
+    #[test]
+    fn test_cancel_from_middle_of_queue_keeps_time_priority() {
+        let mut market = MarketSimulator::new(1.0);
+        let ids: Vec<_> = (0..4)
+            .map(|_| market.place_order(OrderSide::Buy, 100.0, 5).unwrap()[0].order_id)
+            .collect();
+        market.cancel_order(ids[1]).unwrap();
+        market.cancel_order(ids[3]).unwrap();
+        // Rejoins the back of the queue in a freed slot
+        let last = market.place_order(OrderSide::Buy, 100.0, 5).unwrap()[0].order_id;
+
+        market.place_order(OrderSide::Sell, 100.0, 12).unwrap();
+        let fills: Vec<_> = market.get_all_trades().iter().map(|t| (t.buy_order_id, t.quantity)).collect();
+        assert_eq!(fills, vec![(ids[0], 5), (ids[2], 5), (last, 2)]);
+        assert_eq!(market.get_market_depth(1).0, vec![(price(100.0), 3)]);
+    }
+
+    #[test]
+    fn test_evicted_orders_are_found_in_the_archive() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_order_archive(MemoryOrderArchive::new());
+        market
+            .set_retention_policy(RetentionPolicy { evict_closed_orders: true, ..RetentionPolicy::default() })
+            .unwrap();
+
+        let filled = market.place_order(OrderSide::Sell, 101.0, 5).unwrap()[0].order_id;
+        let cancelled = market.place_order(OrderSide::Buy, 99.0, 5).unwrap()[0].order_id;
+        let taker = market.place_order(OrderSide::Buy, 101.0, 8).unwrap()[0].order_id;
+        market.cancel_order(cancelled).unwrap();
+
+        assert!(market.get_order(&filled).is_none());
+        assert_eq!(market.find_order(&filled).unwrap().status, OrderStatus::Filled);
+        assert_eq!(market.find_order(&cancelled).unwrap().status, OrderStatus::Cancelled);
+        // The rest of the taker still works and stays in the book
+        assert_eq!(market.get_order(&taker).unwrap().filled_quantity, 5);
+        assert_eq!(market.get_order_count(), 1);
+    }
+
+    #[test]
+    fn test_trade_tape_spills_beyond_capacity() {
+        let path = std::env::temp_dir().join(format!("market_sim_spill_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut market = MarketSimulator::new(1.0);
+        market
+            .set_retention_policy(RetentionPolicy {
+                trade_capacity: Some(2),
+                trade_spill_path: Some(path.clone()),
+                ..RetentionPolicy::default()
+            })
+            .unwrap();
+
+        for quantity in 1..=5 {
+            market.place_order(OrderSide::Sell, 100.0, quantity).unwrap();
+            market.place_order(OrderSide::Buy, 100.0, quantity).unwrap();
+        }
+
+        assert_eq!(market.get_all_trades().len(), 2);
+        assert_eq!(market.get_trade_count(), 5);
+        assert_eq!(market.get_signed_volume(), 15);
+        let history: Vec<_> = market.get_trade_history().unwrap().iter().map(|t| t.quantity).collect();
+        assert_eq!(history, vec![1, 2, 3, 4, 5]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_trade_spill_failure_refuses_later_requests() {
+        let mut market = MarketSimulator::new(1.0);
+        let retention = RetentionPolicy {
+            trade_capacity: Some(1),
+            trade_spill_path: Some("/dev/full".into()),
+            ..RetentionPolicy::default()
+        };
+        market.set_retention_policy(retention).unwrap();
+        for _ in 0..2 {
+            market.place_order(OrderSide::Sell, 100.0, 1).unwrap();
+            market.place_order(OrderSide::Buy, 100.0, 1).unwrap();
+        }
+
+        // Neither trade is lost, and nothing more trades until the tape is fixed
+        assert_eq!(market.get_all_trades().len(), 2);
+        assert!(matches!(market.place_order(OrderSide::Sell, 100.0, 1), Err(MarketError::Storage(_))));
+        market.set_retention_policy(RetentionPolicy { trade_capacity: Some(1), ..RetentionPolicy::default() }).unwrap();
+        assert_eq!(market.get_all_trades().len(), 1);
+        assert!(market.place_order(OrderSide::Sell, 100.0, 1).is_ok());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_requests_refused_after_a_spill_failure_are_not_journaled() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_journal(Some(Journal::in_memory()));
+        market.deposit(1, 5_000.0).unwrap();
+        let retention = RetentionPolicy {
+            trade_capacity: Some(1),
+            trade_spill_path: Some("/dev/full".into()),
+            ..RetentionPolicy::default()
+        };
+        market.set_retention_policy(retention).unwrap();
+        market.place_order(OrderSide::Sell, 100.0, 2).unwrap();
+        market.place_market_order(OrderSide::Buy, 1).unwrap();
+        market.place_market_order(OrderSide::Buy, 1).unwrap();
+
+        // Nothing changes the market while the tape is broken
+        assert!(matches!(market.deposit(3, 100_000.0), Err(MarketError::Storage(_))));
+        assert!(matches!(market.update(market.now()), Err(MarketError::Storage(_))));
+        assert!(matches!(market.expire_orders(market.now()), Err(MarketError::Storage(_))));
+        assert!(matches!(market.end_of_day(), Err(MarketError::Storage(_))));
+        assert!(market.get_account(3).is_none());
+
+        market.set_retention_policy(RetentionPolicy::default()).unwrap();
+        market.deposit(1, 1_000.0).unwrap();
+        let mut replayed = MarketSimulator::new(1.0);
+        replayed.replay(market.journal().unwrap().entries().to_vec()).unwrap();
+        assert_eq!(replayed.get_account(1), market.get_account(1));
+        assert_eq!(replayed.state_hash(), market.state_hash());
+    }
+
+    #[test]
+    fn test_journal_replay_rebuilds_the_same_state() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_journal(Some(Journal::in_memory()));
+        market.deposit(1, 10_000.0).unwrap();
+        let resting = market.place_order(OrderSide::Buy, 99.0, 10).unwrap()[0].order_id;
+        market.place_order(OrderSide::Sell, 101.0, 10).unwrap();
+        market.amend_order(resting, 99.5, 12).unwrap();
+        market.set_phase(TradingPhase::Halted).unwrap();
+        market.resume().unwrap();
+        market.submit_order(Order::new(OrderSide::Buy, price(101.0), 4).with_account(1)).unwrap();
+        // Rejected live, and again on replay
+        assert!(market.cancel_order(uuid::Uuid::new_v4()).is_err());
+
+        let entries = market.journal().unwrap().entries().to_vec();
+        assert_eq!(entries.len(), 8);
+        let mut replayed = MarketSimulator::new(1.0);
+        replayed.replay(entries.clone()).unwrap();
+        assert_eq!(replayed.state_hash(), market.state_hash());
+        assert_eq!(replayed.get_all_trades().len(), 1);
+
+        let mut gap = entries;
+        gap.remove(2);
+        assert!(matches!(
+            MarketSimulator::new(1.0).replay(gap),
+            Err(MarketError::JournalGap { expected: 3, found: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_saved_exchange_loads_with_queues_and_tape_intact() {
+        let path = std::env::temp_dir().join(format!("market_sim_snapshot_{}.json", std::process::id()));
+        let mut exchange = Exchange::new();
+        exchange.add_instrument("AAPL", Instrument::default(), 1.0).unwrap();
+        let clock = SimulatedClock::new(chrono::Utc::now());
+        let market = exchange.market_mut("AAPL").unwrap();
+        market.set_clock(clock.clone());
+        market.deposit(1, 5_000.0).unwrap();
+        let first = market.place_order(OrderSide::Buy, 99.0, 5).unwrap()[0].order_id;
+        let second = market.place_order(OrderSide::Buy, 99.0, 5).unwrap()[0].order_id;
+        market.submit_order(Order::iceberg(OrderSide::Sell, price(101.0), 30, 10)).unwrap();
+        market.submit_order(Order::stop(OrderSide::Sell, price(98.0), 4)).unwrap();
+        market.submit_order(Order::new(OrderSide::Buy, price(101.0), 12).with_account(1)).unwrap();
+        exchange.save(&path).unwrap();
+
+        let mut restored = Exchange::new();
+        restored.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        restored.market_mut("AAPL").unwrap().set_clock(clock.clone());
+        let (live, saved) = (exchange.market_mut("AAPL").unwrap(), restored.market_mut("AAPL").unwrap());
+        assert_eq!(saved.state_hash(), live.state_hash());
+        assert_eq!(saved.get_all_trades().len(), 2);
+        assert_eq!(saved.get_account(1), live.get_account(1));
+        assert_eq!(saved.get_market_depth(1).1, vec![(price(101.0), 8)]);
+
+        // Both carry on the same way: the first bid keeps its priority
+        let sell = Order::new(OrderSide::Sell, price(99.0), 7);
+        for market in [live, saved] {
+            market.submit_order(sell.clone()).unwrap();
+            assert_eq!(market.get_order(&first).unwrap().status, OrderStatus::Filled);
+            assert_eq!(market.get_order(&second).unwrap().filled_quantity, 2);
+        }
+        assert_eq!(
+            restored.market("AAPL").unwrap().state_hash(),
+            exchange.market("AAPL").unwrap().state_hash()
+        );
+    }
+
+    #[test]
+    fn test_loaded_market_carries_on_issuing_new_ids() {
+        let path = std::env::temp_dir().join(format!("market_sim_ids_{}.json", std::process::id()));
+        let mut market = MarketSimulator::new(1.0);
+        market.set_id_generator(SequentialIds::new());
+        let resting = market.place_order(OrderSide::Sell, 101.0, 10).unwrap()[0].order_id;
+        market.place_order(OrderSide::Buy, 101.0, 4).unwrap();
+        market.save(&path).unwrap();
+
+        let mut loaded = MarketSimulator::new(1.0);
+        loaded.set_id_generator(SequentialIds::new());
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (live, saved) = (market.next_order_id(), loaded.next_order_id());
+        assert_eq!(saved, live);
+
+        // Neither the new order nor its trade reuses an ID from before the save
+        let mut seen: Vec<_> = market.get_all_trades().iter().map(|trade| trade.id).collect();
+        seen.extend([resting, live]);
+        let reports = loaded.place_order(OrderSide::Buy, 101.0, 2).unwrap();
+        assert!(!seen.contains(&reports[0].order_id));
+        assert!(!seen.contains(&loaded.get_all_trades()[1].id));
+        assert_eq!(loaded.get_order(&resting).unwrap().filled_quantity, 6);
+    }
+
+    #[test]
+    fn test_snapshot_without_id_position_still_loads() {
+        let path = std::env::temp_dir().join(format!("market_sim_no_ids_{}.json", std::process::id()));
+        let mut market = MarketSimulator::new(1.0);
+        market.set_id_generator(SequentialIds::new());
+        market.place_order(OrderSide::Sell, 101.0, 10).unwrap();
+        market.save(&path).unwrap();
+        let mut document: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        document["state"].as_object_mut().unwrap().remove("id_position").unwrap();
+        std::fs::write(&path, document.to_string()).unwrap();
+
+        let mut loaded = MarketSimulator::new(1.0);
+        let result = loaded.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert_eq!(loaded.state_hash(), market.state_hash());
+    }
+
+    #[test]
+    fn test_snapshot_of_another_version_is_refused() {
+        let path = std::env::temp_dir().join(format!("market_sim_old_snapshot_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"version": 0, "state": {}}"#).unwrap();
+        let result = Exchange::new().load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            result,
+            Err(MarketError::UnsupportedSnapshotVersion { found: 0, supported: SNAPSHOT_VERSION })
+        );
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let run = |seed: u64| {
+            let clock = SimulatedClock::new(market_open());
+            let mut market = MarketSimulator::new(1.0);
+            market.set_clock(clock.clone());
+            market.set_id_generator(SeededIds::new(seed));
+            let mut reports = market.place_order(OrderSide::Sell, 101.0, 10).unwrap();
+            clock.advance(chrono::Duration::seconds(1));
+            let iceberg = Order::iceberg(OrderSide::Sell, price(102.0), 20, 5).with_id(market.next_order_id());
+            reports.extend(market.submit_order(iceberg).unwrap());
+            clock.advance(chrono::Duration::milliseconds(250));
+            reports.extend(market.place_market_order(OrderSide::Buy, 18).unwrap());
+            serde_json::to_string(&(reports, market.get_all_trades())).unwrap()
+        };
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn test_duplicate_order_ids_are_rejected() {
+        let mut market = MarketSimulator::new(1.0);
+        let resting = market.place_order(OrderSide::Buy, 100.0, 5).unwrap()[0].order_id;
+
+        let duplicate = Order::new(OrderSide::Buy, price(99.0), 7).with_id(resting);
+        assert_eq!(market.submit_order(duplicate), Err(MarketError::DuplicateOrderId(resting)));
+        let order = market.get_order(&resting).unwrap();
+        assert_eq!((order.price, order.quantity), (price(100.0), 5));
+
+        // The resting order still trades and cancels as normal
+        market.place_order(OrderSide::Sell, 100.0, 2).unwrap();
+        assert_eq!(market.get_order(&resting).unwrap().filled_quantity, 2);
+        assert!(market.cancel_order(resting).is_ok());
+        assert_eq!(market.get_best_bid(), None);
+
+        let mut book = OrderBook::new();
+        let resting = book.add_order(Order::new(OrderSide::Sell, price(101.0), 5)).reports[0].order_id;
+        let rejected = book.add_order(Order::new(OrderSide::Sell, price(102.0), 5).with_id(resting));
+        assert_eq!(rejected.reports[0].execution_type, ExecutionType::Rejected);
+        assert_eq!(book.get_market_depth(5).1, [(price(101.0), 5)]);
+    }
+
+    #[test]
+    fn test_simulated_clock_drives_timestamps_and_halts() {
+        let clock = SimulatedClock::new(market_open());
+        let mut market = MarketSimulator::new(1.0);
+        market.set_clock(clock.clone());
+        market.set_id_generator(SequentialIds::new());
+
+        let resting = market.place_order(OrderSide::Sell, 101.0, 5).unwrap();
+        assert_eq!(resting[0].order_id, uuid::Uuid::from_u128(1));
+        clock.advance(chrono::Duration::seconds(30));
+        let reports = market.place_order(OrderSide::Buy, 101.0, 5).unwrap();
+        let trade = &market.get_all_trades()[0];
+        assert_eq!((trade.id, trade.timestamp), (uuid::Uuid::from_u128(3), market_open() + chrono::Duration::seconds(30)));
+        assert!(reports.iter().all(|r| r.timestamp == trade.timestamp));
+        assert_eq!(market.get_order(&resting[0].order_id).unwrap().timestamp, market_open());
+
+        // The default halt lasts five minutes from the time on the clock
+        market.halt().unwrap();
+        clock.advance(chrono::Duration::minutes(5));
+        market.update(market.now()).unwrap();
+        assert_eq!(market.phase(), TradingPhase::Continuous);
+    }
+
+    #[test]
+    fn test_l2_feed_rebuilds_the_published_depth() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Buy, 99.0, 10).unwrap();
+        market.set_market_data(Some(L2Publisher::new(3, Some(4))));
+        let feed = market.subscribe_market_data().unwrap();
+
+        let resting = market.place_order(OrderSide::Buy, 100.0, 5).unwrap()[0].order_id;
+        for (ask, quantity) in [(102.0, 4), (103.0, 6), (104.0, 2), (105.0, 9)] {
+            market.place_order(OrderSide::Sell, ask, quantity).unwrap();
+        }
+        market.place_market_order(OrderSide::Buy, 7).unwrap();
+        market.cancel_order(resting).unwrap();
+
+        let messages: Vec<L2Message> = feed.try_iter().collect();
+        let mut book = L2Book::new();
+        for message in &messages {
+            book.apply(message).unwrap();
+        }
+        assert_eq!(book.get_market_depth(3), market.get_market_depth(3));
+
+        // One snapshot on subscribing, then one after each change that makes up
+        // four more deltas
+        let sequence = market.market_data().unwrap().sequence();
+        let snapshots = messages.iter().filter(|m| matches!(m, L2Message::Snapshot(_))).count();
+        assert_eq!(book.sequence(), Some(sequence));
+        assert_eq!(snapshots as u64, 1 + sequence / 4);
+    }
+
+    #[test]
+    fn test_l2_snapshot_follows_the_whole_batch() {
+        let mut publisher = L2Publisher::new(5, Some(2));
+        let feed = publisher.subscribe();
+        let bids = vec![(price(100.0), 1), (price(99.0), 2), (price(98.0), 3)];
+        publisher.publish(bids.clone(), Vec::new(), market_open());
+
+        // The interval is reached at the second delta, but the snapshot waits for
+        // the third, whose level it already shows
+        let messages: Vec<L2Message> = feed.try_iter().collect();
+        let sequences: Vec<_> = messages.iter().map(L2Message::sequence).collect();
+        assert_eq!(sequences, [0, 1, 2, 3, 3]);
+        let mut book = L2Book::new();
+        book.apply(messages.last().unwrap()).unwrap();
+        assert_eq!(book.get_market_depth(5), (bids, Vec::new()));
+
+        publisher.publish(vec![(price(100.0), 4)], Vec::new(), market_open());
+        for message in feed.try_iter() {
+            book.apply(&message).unwrap();
+        }
+        assert_eq!(book.get_market_depth(5), (vec![(price(100.0), 4)], Vec::new()));
+    }
+
+    #[test]
+    fn test_l2_feed_gap_waits_for_the_next_snapshot() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_market_data(Some(L2Publisher::new(5, Some(3))));
+        let feed = market.subscribe_market_data().unwrap();
+        for (bid, quantity) in [(100.0, 1), (99.0, 2), (98.0, 3), (97.0, 4)] {
+            market.place_order(OrderSide::Buy, bid, quantity).unwrap();
+        }
+
+        // Deltas 1 to 3, the snapshot at 3, then delta 4; delta 2 is lost
+        let mut book = L2Book::new();
+        let messages: Vec<L2Message> = feed.try_iter().collect();
+        book.apply(&messages[0]).unwrap();
+        book.apply(&messages[1]).unwrap();
+        assert_eq!(book.apply(&messages[3]), Err(MarketError::FeedGap { expected: 2, found: 3 }));
+        assert!(!book.is_synced());
+
+        book.apply(&messages[5]).unwrap();
+        assert!(!book.is_synced());
+        assert!(matches!(messages[4], L2Message::Snapshot(_)));
+        book.apply(&messages[4]).unwrap();
+        book.apply(&messages[5]).unwrap();
+        assert_eq!(book.sequence(), Some(4));
+        assert_eq!(book.get_market_depth(5), market.get_market_depth(5));
+    }
+
+    #[test]
+    fn test_l3_feed_rebuilds_every_resting_order() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Buy, 99.0, 10).unwrap();
+        let feed = market.subscribe_order_events();
+        let mut book = L3Book::new();
+        let mut check = |market: &MarketSimulator| {
+            for message in feed.try_iter() {
+                book.apply(&message).unwrap();
+            }
+            assert!(book.matches(market.order_book()));
+            assert_eq!(book.get_market_depth(10), market.get_market_depth(10));
+        };
+
+        let bid = market.place_order(OrderSide::Buy, 100.0, 6).unwrap()[0].order_id;
+        market.place_order(OrderSide::Buy, 100.0, 4).unwrap();
+        market.submit_order(Order::iceberg(OrderSide::Sell, price(102.0), 12, 5)).unwrap();
+        market.place_order(OrderSide::Sell, 103.0, 7).unwrap();
+        check(&market);
+
+        // Takes a whole iceberg slice and part of the one refreshed after it
+        market.place_market_order(OrderSide::Buy, 8).unwrap();
+        check(&market);
+        market.amend_order(bid, 100.0, 2).unwrap();
+        check(&market);
+        market.amend_order(bid, 98.0, 2).unwrap();
+        check(&market);
+        market.place_market_order(OrderSide::Sell, 5).unwrap();
+        check(&market);
+        market.cancel_order(bid).unwrap();
+        check(&market);
+    }
+
+    #[test]
+    fn test_l3_feed_reports_auction_executions_and_stp_reductions() {
+        let mut market = MarketSimulator::new(1.0);
+        let feed = market.subscribe_order_events();
+        market.set_phase(TradingPhase::Closed).unwrap();
+        market.set_phase(TradingPhase::PreOpenAuction).unwrap();
+        // The auction trades past the displayed slice into the reserve
+        let iceberg = Order::iceberg(OrderSide::Sell, price(100.0), 6, 3).with_account(1);
+        market.submit_order(iceberg).unwrap();
+        market.place_order(OrderSide::Buy, 100.0, 4).unwrap();
+        market.set_phase(TradingPhase::Continuous).unwrap();
+
+        // Self-trade against what is left of the iceberg decrements it
+        let own = Order::new(OrderSide::Buy, price(100.0), 1)
+            .with_account(1)
+            .with_self_trade_prevention(SelfTradePrevention::DecrementAndCancel);
+        market.submit_order(own).unwrap();
+
+        let messages: Vec<L3Message> = feed.try_iter().collect();
+        let kinds: Vec<(OrderEventKind, u64)> = messages
+            .iter()
+            .filter_map(|m| match m {
+                L3Message::Event(event) => Some((event.kind, event.quantity)),
+                L3Message::Snapshot(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (OrderEventKind::Add, 3),
+                (OrderEventKind::Add, 4),
+                (OrderEventKind::Execute, 4),
+                (OrderEventKind::Execute, 4),
+                (OrderEventKind::Add, 2),
+                (OrderEventKind::Reduce, 1),
+            ]
+        );
+
+        let mut book = L3Book::new();
+        for message in &messages {
+            book.apply(message).unwrap();
+        }
+        assert!(book.matches(market.order_book()));
+        assert_eq!(market.get_market_depth(1).1, vec![(price(100.0), 1)]);
+    }
+
+    fn event_names(log: &EventLog) -> Vec<&'static str> {
+        log.take()
+            .iter()
+            .map(|event| match event {
+                MarketEvent::OrderAccepted(_) => "accepted",
+                MarketEvent::OrderRejected(_) => "rejected",
+                MarketEvent::Traded(_) => "traded",
+                MarketEvent::OrderCancelled(_) => "cancelled",
+                MarketEvent::TopOfBookChanged { .. } => "top",
+                MarketEvent::PhaseChanged { .. } => "phase",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_observers_see_events_in_order() {
+        let mut market = MarketSimulator::new(1.0);
+        market.place_order(OrderSide::Sell, 101.0, 5).unwrap();
+        market.place_order(OrderSide::Sell, 102.0, 5).unwrap();
+        let (first, second) = (EventLog::new(), EventLog::new());
+        market.add_observer(first.clone());
+        market.add_observer(second.clone());
+
+        // Sweeps both levels and cancels the remainder
+        market.set_fee_schedule(FeeSchedule::new(FeeRate::BasisPoints(0.0), FeeRate::BasisPoints(10.0)));
+        market.place_market_order(OrderSide::Buy, 12).unwrap();
+        let events = second.events();
+        assert_eq!(event_names(&first), ["accepted", "traded", "traded", "cancelled", "top"]);
+        assert_eq!(event_names(&second), ["accepted", "traded", "traded", "cancelled", "top"]);
+        match (&events[1], &events[4]) {
+            (MarketEvent::Traded(trade), MarketEvent::TopOfBookChanged { top_of_book, .. }) => {
+                assert_eq!((trade.price, trade.quantity), (price(101.0), 5));
+                assert!(trade.buy_fee > 0);
+                assert_eq!(*top_of_book, TopOfBook { bid: None, ask: None });
+            }
+            other => panic!("unexpected events {other:?}"),
+        }
+
+        // Refused by the simulator, so the book and its top are untouched
+        market.place_order(OrderSide::Buy, 100.0, 5).unwrap();
+        assert!(matches!(market.place_order(OrderSide::Sell, 100.5, 5), Err(MarketError::SpreadViolation { .. })));
+        assert_eq!(event_names(&first), ["accepted", "top", "rejected"]);
+    }
+
+    #[test]
+    fn test_observers_see_phase_changes_and_cancels() {
+        let mut market = MarketSimulator::new(1.0);
+        let log = EventLog::new();
+        market.add_observer(log.clone());
+
+        market.set_phase(TradingPhase::Closed).unwrap();
+        market.set_phase(TradingPhase::PreOpenAuction).unwrap();
+        market.place_order(OrderSide::Buy, 100.0, 5).unwrap();
+        let sell = market.place_order(OrderSide::Sell, 100.0, 8).unwrap()[0].order_id;
+        assert_eq!(event_names(&log), ["phase", "phase", "accepted", "top", "accepted", "top"]);
+
+        // The phase changes before the uncross trades
+        market.set_phase(TradingPhase::Continuous).unwrap();
+        assert_eq!(event_names(&log), ["phase", "traded", "top"]);
+        market.cancel_order(sell).unwrap();
+        assert_eq!(event_names(&log), ["cancelled", "top"]);
+    }
+
+    #[test]
+    fn test_observers_see_a_circuit_breaker_halt_after_the_trade_that_tripped_it() {
+        let mut market = MarketSimulator::new(1.0);
+        market.set_halt_policy(breaker_policy(HaltedOrderHandling::Reject));
+        for ask in [100.0, 110.0, 120.0] {
+            market.place_order(OrderSide::Sell, ask, 5).unwrap();
+        }
+        let log = EventLog::new();
+        market.add_observer(log.clone());
+
+        market.place_market_order(OrderSide::Buy, 15).unwrap();
+        let events = log.events();
+        assert_eq!(event_names(&log), ["accepted", "traded", "traded", "phase", "cancelled", "top"]);
+        match (&events[2], &events[3]) {
+            (MarketEvent::Traded(trade), MarketEvent::PhaseChanged { from, to, .. }) => {
+                assert_eq!(trade.price, price(110.0));
+                assert_eq!((*from, *to), (TradingPhase::Continuous, TradingPhase::Halted));
+            }
+            other => panic!("unexpected events {other:?}"),
+        }
+    }
 }
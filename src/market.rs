@@ -1,73 +1,307 @@
-use crate::order_book::{Order, OrderBook, OrderSide, Trade, OrderId, Price, Quantity};
+use crate::order_book::{
+    AccountId, DepthLevels, Order, OrderBook, OrderId, OrderSide, OrderStatus, OrderType, Price, Quantity,
+    SelfTradePrevention, Trade,
+};
+use crate::account::{Account, AccountLedger, Cash};
+use crate::auction::{IndicativeUncross, TradingPhase};
+use crate::clock::{Clock, WallClock};
+use crate::error::MarketError;
+use crate::execution::{ExecutionReport, ExecutionType};
+use crate::fees::FeeSchedule;
+use crate::execution::Execution;
+use crate::halt::{Breach, Halt, HaltPolicy, HaltReason, HaltedOrderHandling, PriceWindow, Reopen};
+use crate::history::{OrderArchive, RetentionPolicy, TradeTape};
+use crate::ids::IdGenerator;
+use crate::instrument::Instrument;
+use crate::journal::{Command, Journal, JournalEntry};
+use crate::market_data::{L2Message, L2Publisher, L3Message};
+use crate::matching::MatchingPolicy;
+use crate::observer::{MarketEvent, MarketObserver, TopOfBook};
+use crate::risk::{self, RiskCheck, RiskContext, RiskRejection};
+use crate::snapshot::{self, MarketSnapshot};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct MarketSimulator {
     order_book: OrderBook,
-    trades: Vec<Trade>,
+    clock: Arc<dyn Clock>,
+    trades: TradeTape,
+    // Running hash over every trade recorded, for `state_hash`
+    tape_hash: u64,
     minimum_spread_percentage: f64,
+    instrument: Instrument,
+    accounts: AccountLedger,
+    risk_checks: Vec<Box<dyn RiskCheck>>,
+    fee_schedule: FeeSchedule,
+    // Self-trade prevention for orders that do not set their own mode
+    self_trade_prevention: BTreeMap<AccountId, SelfTradePrevention>,
+    // Net of rebates paid out
+    fees_collected: Cash,
+    phase: TradingPhase,
+    halt_policy: HaltPolicy,
+    halt: Option<Halt>,
+    // Orders received while halted, in arrival order
+    queued_orders: Vec<Order>,
+    // When a reopening auction after a halt uncrosses
+    auction_ends_at: Option<DateTime<Utc>>,
+    retention: RetentionPolicy,
+    // Receives closed orders when the retention policy evicts them
+    order_archive: Option<Box<dyn OrderArchive>>,
+    journal: Option<Journal>,
+    market_data: Option<L2Publisher>,
+    observers: Vec<Box<dyn MarketObserver>>,
+    // As observers were last told it
+    top_of_book: TopOfBook,
 }
 
 impl MarketSimulator {
     pub fn new(minimum_spread_percentage: f64) -> Self {
+        Self::with_instrument(minimum_spread_percentage, Instrument::default())
+    }
+
+    pub fn with_instrument(minimum_spread_percentage: f64, instrument: Instrument) -> Self {
         Self {
             order_book: OrderBook::new(),
-            trades: Vec::new(),
+            clock: Arc::new(WallClock),
+            trades: TradeTape::new(),
+            tape_hash: 0,
             minimum_spread_percentage,
+            instrument,
+            accounts: AccountLedger::new(),
+            risk_checks: Vec::new(),
+            fee_schedule: FeeSchedule::default(),
+            self_trade_prevention: BTreeMap::new(),
+            fees_collected: 0,
+            phase: TradingPhase::Continuous,
+            halt_policy: HaltPolicy::default(),
+            halt: None,
+            queued_orders: Vec::new(),
+            auction_ends_at: None,
+            retention: RetentionPolicy::default(),
+            order_archive: None,
+            journal: None,
+            market_data: None,
+            observers: Vec::new(),
+            top_of_book: TopOfBook::default(),
+        }
+    }
+
+    // A simulator in the saved state, with the default matching policy, clock and
+    // ID generator and no risk checks, order archive or journal
+    pub fn from_snapshot(snapshot: MarketSnapshot) -> Result<Self, MarketError> {
+        let mut market = Self::new(snapshot.minimum_spread_percentage);
+        market.restore(snapshot)?;
+        Ok(market)
+    }
+
+    pub fn instrument(&self) -> &Instrument {
+        &self.instrument
+    }
+
+    // Current time by the simulator's clock
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    // Stamps orders, reports, phase changes and halts from now on; the wall clock
+    // by default
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Arc::new(clock);
+    }
+
+    // Numbers the orders placed through the simulator and every trade; random by
+    // default
+    pub fn set_id_generator<G: IdGenerator + 'static>(&mut self, ids: G) {
+        self.order_book.set_id_generator(Arc::new(ids));
+    }
+
+    // An ID from the simulator's generator, for orders built to pass to `submit_order`
+    pub fn next_order_id(&self) -> OrderId {
+        self.order_book.id_generator().next_id()
+    }
+
+    pub fn place_order(&mut self, side: OrderSide, price: f64, quantity: Quantity) -> Result<Vec<ExecutionReport>, MarketError> {
+        let price = self.instrument.to_price(price)?;
+        self.submit_order(Order::new(side, price, quantity).with_id(self.next_order_id()))
+    }
+
+    // Sweeps the opposite side until filled or the book is exhausted. Any unfilled
+    // remainder is cancelled, which the returned reports end with.
+    pub fn place_market_order(&mut self, side: OrderSide, quantity: Quantity) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.submit_order(Order::market(side, quantity).with_id(self.next_order_id()))
+    }
+
+    // Returns a report for every order the submission touched: the new order's
+    // acknowledgement first, then each fill on both sides, any cancel or expiry,
+    // and the fills of stops it triggered. The order's timestamp is set to its
+    // arrival time by the simulator's clock, and its trades happen at that time.
+    pub fn submit_order(&mut self, mut order: Order) -> Result<Vec<ExecutionReport>, MarketError> {
+        order.timestamp = self.clock.now();
+        self.catch_up(order.timestamp)?;
+        self.record(order.timestamp, || Command::Submit(order.clone()))?;
+        self.submit(order)
+    }
+
+    // Observers hear of an order the simulator refuses as a rejection, and of
+    // everything else through the reports it is given
+    fn submit(&mut self, order: Order) -> Result<Vec<ExecutionReport>, MarketError> {
+        let submitted = (!self.observers.is_empty()).then(|| order.clone());
+        let result = self.enter(order);
+        if let (Err(err), Some(order)) = (&result, submitted) {
+            let report = ExecutionReport::rejected(&order, &err.to_string(), order.timestamp);
+            self.notify(MarketEvent::OrderRejected(report));
         }
+        result
     }
 
-    pub fn place_order(&mut self, side: OrderSide, price: f64, quantity: Quantity) -> Result<OrderId, String> {
-        // Check minimum spread requirement
-        if let Err(msg) = self.validate_order_spread(side, price) {
-            return Err(msg);
+    fn enter(&mut self, mut order: Order) -> Result<Vec<ExecutionReport>, MarketError> {
+        if order.quantity == 0 {
+            return Err(MarketError::ZeroQuantity);
+        }
+        if order.peak_quantity == Some(0) {
+            return Err(MarketError::ZeroPeakQuantity);
+        }
+        if self.order_book.get_order(&order.id).is_some() || self.queued_orders.iter().any(|o| o.id == order.id) {
+            return Err(MarketError::DuplicateOrderId(order.id));
+        }
+
+        // Market and stop-market orders carry no limit price of their own
+        let limit_price = matches!(order.order_type, OrderType::Limit | OrderType::StopLimit).then_some(order.price);
+        let prices = [limit_price, order.stop_price];
+        for price in prices.into_iter().flatten() {
+            self.instrument.validate_price(price)?;
         }
 
-        let order = Order::new(side, price, quantity);
-        let order_id = order.id;
-        
-        let new_trades = self.order_book.add_order(order);
-        self.trades.extend(new_trades);
-        
-        Ok(order_id)
+        match self.phase {
+            TradingPhase::Closed => return Err(MarketError::NotAllowedInPhase(self.phase)),
+            TradingPhase::Halted => match self.halt_policy.order_handling {
+                HaltedOrderHandling::Reject => return Err(MarketError::NotAllowedInPhase(self.phase)),
+                HaltedOrderHandling::Queue => {
+                    let reports = vec![ExecutionReport::new(&order, ExecutionType::Queued, order.timestamp)];
+                    self.queued_orders.push(order);
+                    self.notify_reports(&reports, &[], None);
+                    return Ok(reports);
+                }
+            },
+            // Auctions only collect orders that can wait for the uncross
+            phase if phase.is_auction() && !order.can_rest() && !order.is_stop() => {
+                return Err(MarketError::NotAllowedInPhase(phase));
+            }
+            _ => {}
+        }
+
+        self.check_risk(&order)?;
+        if let Some(price) = limit_price {
+            validate_notional(price, order.quantity)?;
+        }
+
+        // Check minimum spread requirement; market, IOC and FOK orders never rest and
+        // stops wait in the hidden trigger book, so none of them can narrow the spread.
+        // An auction book is allowed to cross.
+        if order.can_rest() && !self.phase.is_auction() {
+            self.validate_order_spread(order.side, order.price)?;
+        }
+
+        if order.self_trade_prevention.is_none() {
+            order.self_trade_prevention = self.self_trade_prevention.get(&order.account_id).copied();
+        }
+
+        let execution = self.order_book.add_order(order);
+        Ok(self.settle(execution))
+    }
+
+    // Default self-trade prevention for the account's orders; `None` turns it off.
+    // A mode set on the order itself takes precedence.
+    pub fn set_self_trade_prevention(&mut self, account_id: AccountId, mode: Option<SelfTradePrevention>) {
+        match mode {
+            Some(mode) => self.self_trade_prevention.insert(account_id, mode),
+            None => self.self_trade_prevention.remove(&account_id),
+        };
+    }
+
+    pub fn add_risk_check<C: RiskCheck + 'static>(&mut self, check: C) {
+        self.risk_checks.push(Box::new(check));
     }
 
-    fn validate_order_spread(&self, side: OrderSide, price: f64) -> Result<(), String> {
+    // Runs every registered risk check in registration order; the first rejection wins
+    fn check_risk(&self, order: &Order) -> Result<(), RiskRejection> {
+        if self.risk_checks.is_empty() {
+            return Ok(());
+        }
+
+        let mut context = RiskContext {
+            account: self.accounts.get_account(order.account_id),
+            open_buy_notional: 0,
+            open_buy_quantity: 0,
+            open_sell_quantity: 0,
+            last_trade_price: self.get_last_trade_price(),
+            best_bid: self.get_best_bid(),
+            best_ask: self.get_best_ask(),
+        };
+
+        // An amended order is checked in its new form, so leave its old one out
+        let open_orders = self
+            .order_book
+            .open_orders()
+            .filter(|o| o.account_id == order.account_id && o.id != order.id);
+        for open in open_orders {
+            let remaining = open.remaining_quantity();
+            match open.side {
+                OrderSide::Buy => {
+                    let price = context.expected_price(open).unwrap_or(0);
+                    context.open_buy_notional = context.open_buy_notional.saturating_add(risk::notional(price, remaining));
+                    context.open_buy_quantity = context.open_buy_quantity.saturating_add(remaining);
+                }
+                OrderSide::Sell => context.open_sell_quantity = context.open_sell_quantity.saturating_add(remaining),
+            }
+        }
+
+        for check in &self.risk_checks {
+            check.check(order, &context)?;
+        }
+        Ok(())
+    }
+
+    fn validate_order_spread(&self, side: OrderSide, price: Price) -> Result<(), MarketError> {
         match side {
             OrderSide::Buy => {
-                if let Some(best_ask) = self.order_book.get_best_ask() {
-                    let ask_price = best_ask.into_inner();
+                if let Some(ask_price) = self.order_book.get_best_ask() {
                     // Allow buy orders at or above the best ask price (they will execute immediately)
                     if price >= ask_price {
                         return Ok(());
                     }
                     
-                    let mid_price = (price + ask_price) / 2.0;
-                    let spread_percentage = (ask_price - price) / mid_price * 100.0;
+                    let spread_percentage = spread_percentage(price, ask_price);
                     
                     if spread_percentage < self.minimum_spread_percentage {
-                        return Err(format!(
-                            "Buy order would create spread of {:.2}%, minimum required is {:.2}%",
-                            spread_percentage, self.minimum_spread_percentage
-                        ));
+                        return Err(MarketError::SpreadViolation {
+                            side,
+                            spread_percentage,
+                            minimum_spread_percentage: self.minimum_spread_percentage,
+                        });
                     }
                 }
             }
             OrderSide::Sell => {
-                if let Some(best_bid) = self.order_book.get_best_bid() {
-                    let bid_price = best_bid.into_inner();
+                if let Some(bid_price) = self.order_book.get_best_bid() {
                     // Allow sell orders at or below the best bid price (they will execute immediately)
                     if price <= bid_price {
                         return Ok(());
                     }
                     
-                    let mid_price = (bid_price + price) / 2.0;
-                    let spread_percentage = (price - bid_price) / mid_price * 100.0;
+                    let spread_percentage = spread_percentage(bid_price, price);
                     
                     if spread_percentage < self.minimum_spread_percentage {
-                        return Err(format!(
-                            "Sell order would create spread of {:.2}%, minimum required is {:.2}%",
-                            spread_percentage, self.minimum_spread_percentage
-                        ));
+                        return Err(MarketError::SpreadViolation {
+                            side,
+                            spread_percentage,
+                            minimum_spread_percentage: self.minimum_spread_percentage,
+                        });
                     }
                 }
             }
@@ -75,14 +309,748 @@ impl MarketSimulator {
         Ok(())
     }
 
-    pub fn cancel_order(&mut self, order_id: OrderId) -> Option<Order> {
-        self.order_book.cancel_order(order_id)
+    // Cancels are accepted in every phase, including orders queued during a halt
+    pub fn cancel_order(&mut self, order_id: OrderId) -> Result<ExecutionReport, MarketError> {
+        let now = self.clock.now();
+        self.catch_up(now)?;
+        self.record(now, || Command::Cancel(order_id))?;
+        self.cancel(order_id, now)
+    }
+
+    fn cancel(&mut self, order_id: OrderId, now: DateTime<Utc>) -> Result<ExecutionReport, MarketError> {
+        if let Some(index) = self.queued_orders.iter().position(|o| o.id == order_id) {
+            let mut order = self.queued_orders.remove(index);
+            order.status = OrderStatus::Cancelled;
+            let report = ExecutionReport::new(&order, ExecutionType::Cancelled, now);
+            self.notify_reports(std::slice::from_ref(&report), &[], None);
+            return Ok(report);
+        }
+
+        let order = self.order_book.get_order(&order_id).ok_or(MarketError::UnknownOrder(order_id))?;
+        match order.status {
+            OrderStatus::Filled => return Err(MarketError::OrderAlreadyFilled(order_id)),
+            OrderStatus::Cancelled | OrderStatus::Expired | OrderStatus::Rejected => {
+                return Err(MarketError::OrderClosed { order_id, status: order.status });
+            }
+            OrderStatus::Open | OrderStatus::PartiallyFilled => {}
+        }
+
+        let order = self.order_book.cancel_order(order_id, now).ok_or(MarketError::UnknownOrder(order_id))?;
+        let report = ExecutionReport::new(&order, ExecutionType::Cancelled, now);
+        self.archive_orders(vec![order]);
+        self.notify_reports(std::slice::from_ref(&report), &[], None);
+        self.publish_market_data(now);
+        Ok(report)
+    }
+
+    // Reports the replacement, followed by any fills if the new price crosses
+    pub fn amend_order(
+        &mut self,
+        order_id: OrderId,
+        new_price: f64,
+        new_quantity: Quantity,
+    ) -> Result<Vec<ExecutionReport>, MarketError> {
+        let new_price = self.instrument.to_price(new_price)?;
+        let now = self.clock.now();
+        self.catch_up(now)?;
+        self.record(now, || Command::Amend {
+            order_id,
+            price: new_price,
+            quantity: new_quantity,
+        })?;
+        self.amend(order_id, new_price, new_quantity, now)
+    }
+
+    fn amend(
+        &mut self,
+        order_id: OrderId,
+        new_price: Price,
+        new_quantity: Quantity,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<ExecutionReport>, MarketError> {
+        let order = self.order_book.get_order(&order_id).ok_or(MarketError::UnknownOrder(order_id))?;
+        match order.status {
+            OrderStatus::Filled => return Err(MarketError::OrderAlreadyFilled(order_id)),
+            OrderStatus::Cancelled | OrderStatus::Expired | OrderStatus::Rejected => {
+                return Err(MarketError::OrderClosed { order_id, status: order.status });
+            }
+            OrderStatus::Open | OrderStatus::PartiallyFilled => {}
+        }
+        if order.order_type != OrderType::Limit {
+            return Err(MarketError::NotAmendable(order_id));
+        }
+        if matches!(self.phase, TradingPhase::Closed | TradingPhase::Halted) {
+            return Err(MarketError::NotAllowedInPhase(self.phase));
+        }
+        if new_quantity <= order.filled_quantity {
+            return Err(MarketError::AmendBelowFilled {
+                new_quantity,
+                filled_quantity: order.filled_quantity,
+            });
+        }
+
+        let mut amended = order.clone();
+        amended.price = new_price;
+        amended.quantity = new_quantity;
+        self.check_risk(&amended)?;
+        validate_notional(new_price, new_quantity)?;
+
+        // Only a price change can narrow the spread
+        if new_price != order.price && !self.phase.is_auction() {
+            self.validate_order_spread(order.side, new_price)?;
+        }
+
+        let execution = self
+            .order_book
+            .amend_order(order_id, new_price, new_quantity, now)
+            .ok_or(MarketError::NotAmendable(order_id))?;
+        Ok(self.settle(execution))
+    }
+
+    pub fn phase(&self) -> TradingPhase {
+        self.phase
+    }
+
+    // Moves to the next trading phase. Entering an auction starts collecting
+    // orders; leaving one uncrosses it, and opening into continuous trading then
+    // releases any stops the uncross triggered. Moving to `Halted` is a manual
+    // halt; leaving a halt submits the orders queued during it, or cancels them if
+    // the market closes. Returns the resulting reports.
+    pub fn set_phase(&mut self, phase: TradingPhase) -> Result<Vec<ExecutionReport>, MarketError> {
+        let now = self.clock.now();
+        self.record(now, || Command::SetPhase(phase))?;
+        self.change_phase(phase, now)
+    }
+
+    fn change_phase(&mut self, phase: TradingPhase, now: DateTime<Utc>) -> Result<Vec<ExecutionReport>, MarketError> {
+        if !self.phase.can_move_to(phase) {
+            return Err(MarketError::InvalidPhaseTransition { from: self.phase, to: phase });
+        }
+
+        let previous = std::mem::replace(&mut self.phase, phase);
+        self.notify(MarketEvent::PhaseChanged {
+            from: previous,
+            to: phase,
+            timestamp: now,
+        });
+        let mut reports = Vec::new();
+        if previous.is_auction() {
+            self.auction_ends_at = None;
+            let execution = self.order_book.uncross(self.get_last_trade_price(), now);
+            reports.extend(self.settle(execution));
+        }
+        if phase.is_auction() {
+            self.order_book.begin_auction();
+        }
+        if phase == TradingPhase::Halted {
+            self.begin_halt(HaltReason::Manual, now);
+        }
+        if previous == TradingPhase::Halted {
+            self.halt = None;
+            self.order_book.set_price_window(PriceWindow::default());
+        }
+        if phase == TradingPhase::Continuous {
+            let execution = self.order_book.release_stops(now);
+            reports.extend(self.settle(execution));
+        }
+        if previous == TradingPhase::Halted {
+            reports.extend(self.release_queued_orders(now));
+        }
+
+        Ok(reports)
+    }
+
+    pub fn halt_policy(&self) -> &HaltPolicy {
+        &self.halt_policy
+    }
+
+    pub fn set_halt_policy(&mut self, halt_policy: HaltPolicy) {
+        self.order_book.set_circuit_breaker(halt_policy.circuit_breaker);
+        self.halt_policy = halt_policy;
+    }
+
+    // The halt in force, if the market is halted
+    pub fn get_halt(&self) -> Option<&Halt> {
+        self.halt.as_ref()
+    }
+
+    pub fn get_queued_orders(&self) -> &[Order] {
+        &self.queued_orders
+    }
+
+    // Halts continuous trading by hand; the halt ends like any other, after the
+    // policy's halt duration or on `resume`
+    pub fn halt(&mut self) -> Result<(), MarketError> {
+        self.set_phase(TradingPhase::Halted).map(|_| ())
+    }
+
+    // Ends the current halt now instead of waiting for it to run its course
+    pub fn resume(&mut self) -> Result<Vec<ExecutionReport>, MarketError> {
+        if self.phase != TradingPhase::Halted {
+            return Err(MarketError::InvalidPhaseTransition {
+                from: self.phase,
+                to: TradingPhase::Continuous,
+            });
+        }
+        let now = self.clock.now();
+        self.record(now, || Command::Resume)?;
+        Ok(self.reopen(now))
+    }
+
+    // Moves the market on to `now`: a halt whose time is up reopens, and a
+    // reopening auction that has run its course uncrosses
+    pub fn update(&mut self, now: DateTime<Utc>) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.record(now, || Command::Update(now))?;
+        Ok(self.advance(now))
+    }
+
+    fn advance(&mut self, now: DateTime<Utc>) -> Vec<ExecutionReport> {
+        match self.phase {
+            TradingPhase::Halted if self.halt_ends_by(now) => self.reopen(now),
+            TradingPhase::PreOpenAuction if self.auction_ends_by(now) => self
+                .change_phase(TradingPhase::Continuous, now)
+                .expect("an auction can always open into continuous trading"),
+            _ => Vec::new(),
+        }
+    }
+
+    fn halt_ends_by(&self, now: DateTime<Utc>) -> bool {
+        self.halt.is_some_and(|halt| now >= halt.resumes_at)
+    }
+
+    fn auction_ends_by(&self, now: DateTime<Utc>) -> bool {
+        self.auction_ends_at.is_some_and(|end| now >= end)
+    }
+
+    // Requests move the market on to their arrival time first, so a halt or
+    // reopening auction that has run its course ends without waiting for `update`.
+    // The update is journaled ahead of the request, and its reports go to
+    // observers only.
+    fn catch_up(&mut self, now: DateTime<Utc>) -> Result<(), MarketError> {
+        let due = match self.phase {
+            TradingPhase::Halted => self.halt_ends_by(now),
+            TradingPhase::PreOpenAuction => self.auction_ends_by(now),
+            _ => false,
+        };
+        if due {
+            self.update(now)?;
+        }
+        Ok(())
+    }
+
+    fn begin_halt(&mut self, reason: HaltReason, at: DateTime<Utc>) {
+        self.phase = TradingPhase::Halted;
+        self.halt = Some(Halt {
+            reason,
+            halted_at: at,
+            resumes_at: at + self.halt_policy.halt_duration,
+        });
+    }
+
+    fn reopen(&mut self, now: DateTime<Utc>) -> Vec<ExecutionReport> {
+        let (phase, auction_ends_at) = match self.halt_policy.reopen {
+            Reopen::Continuous => (TradingPhase::Continuous, None),
+            Reopen::Auction(duration) => (TradingPhase::PreOpenAuction, Some(now + duration)),
+        };
+        let reports = self.change_phase(phase, now).expect("a halted market can always reopen");
+        self.auction_ends_at = auction_ends_at;
+        reports
+    }
+
+    // Queued orders go through every check again as if they had just arrived, and
+    // are stamped with the reopening time; any that now fail are reported as rejected
+    fn release_queued_orders(&mut self, now: DateTime<Utc>) -> Vec<ExecutionReport> {
+        let mut reports = Vec::new();
+        for mut order in std::mem::take(&mut self.queued_orders) {
+            if self.phase == TradingPhase::Closed {
+                order.status = OrderStatus::Cancelled;
+                let report = ExecutionReport::new(&order, ExecutionType::Cancelled, now);
+                self.notify_reports(std::slice::from_ref(&report), &[], None);
+                reports.push(report);
+                continue;
+            }
+            order.timestamp = now;
+            match self.submit(order.clone()) {
+                Ok(new_reports) => reports.extend(new_reports),
+                Err(err) => reports.push(ExecutionReport::rejected(&order, &err.to_string(), now)),
+            }
+        }
+        reports
+    }
+
+    // Published while an auction is collecting orders
+    pub fn get_indicative_uncross(&self) -> Option<IndicativeUncross> {
+        if !self.phase.is_auction() {
+            return None;
+        }
+        self.order_book.indicative_uncross(self.get_last_trade_price())
+    }
+
+    pub fn expire_orders(&mut self, now: DateTime<Utc>) -> Result<Vec<ExecutionReport>, MarketError> {
+        self.record(now, || Command::ExpireOrders(now))?;
+        Ok(self.expire(now))
+    }
+
+    fn expire(&mut self, now: DateTime<Utc>) -> Vec<ExecutionReport> {
+        let expired = self.order_book.expire_orders(now);
+        let reports = expiry_reports(&expired, now);
+        self.archive_orders(expired);
+        self.notify_reports(&reports, &[], None);
+        self.publish_market_data(now);
+        reports
+    }
+
+    // Closes the trading day: every resting DAY order is expired
+    pub fn end_of_day(&mut self) -> Result<Vec<ExecutionReport>, MarketError> {
+        let now = self.clock.now();
+        self.record(now, || Command::EndOfDay)?;
+        Ok(self.close_day(now))
+    }
+
+    fn close_day(&mut self, now: DateTime<Utc>) -> Vec<ExecutionReport> {
+        let expired = self.order_book.expire_day_orders(now);
+        let reports = expiry_reports(&expired, now);
+        self.archive_orders(expired);
+        self.notify_reports(&reports, &[], None);
+        self.publish_market_data(now);
+        reports
+    }
+
+    pub fn get_last_trade_price(&self) -> Option<Price> {
+        self.order_book.get_last_trade_price()
+    }
+
+    // Books the outcome of a call into the order book: its trades are recorded
+    // and any orders it closed are archived. Returns its reports.
+    fn settle(&mut self, mut execution: Execution) -> Vec<ExecutionReport> {
+        if let Some(breach) = &execution.breach {
+            let reason = HaltReason::CircuitBreaker {
+                trade_price: breach.trade_price,
+                reference_price: breach.reference_price,
+            };
+            self.begin_halt(reason, execution.timestamp);
+        }
+        if let Some(order) = execution.interrupted.take() {
+            let report = self.hold_interrupted_order(order, execution.timestamp);
+            execution.reports.push(report);
+        }
+        let trades = self.record_trades(execution.trades);
+        let closed = self.order_book.drain_closed_orders();
+        self.archive_orders(closed);
+        self.notify_reports(&execution.reports, &trades, execution.breach.as_ref());
+        self.publish_market_data(execution.timestamp);
+        execution.reports
+    }
+
+    // What is left of a resting order whose trade tripped the circuit breaker is
+    // treated like an order arriving during the halt: queued for the reopening, or
+    // cancelled. Orders that cannot rest never get here; the book cancels their
+    // remainder as usual.
+    fn hold_interrupted_order(&mut self, mut order: Order, now: DateTime<Utc>) -> ExecutionReport {
+        match self.halt_policy.order_handling {
+            HaltedOrderHandling::Queue => {
+                let report = ExecutionReport::new(&order, ExecutionType::Queued, now);
+                self.queued_orders.push(order);
+                report
+            }
+            HaltedOrderHandling::Reject => {
+                order.status = OrderStatus::Cancelled;
+                let mut report = ExecutionReport::new(&order, ExecutionType::Cancelled, now);
+                report.reason = Some(MarketError::NotAllowedInPhase(self.phase).to_string());
+                self.order_book.close_order(order);
+                report
+            }
+        }
+    }
+
+    // Sends subscribers whatever the last change did to the published levels,
+    // and observers the new top of book if it moved
+    fn publish_market_data(&mut self, now: DateTime<Utc>) {
+        if let Some(publisher) = &mut self.market_data {
+            let (bids, asks) = self.order_book.get_market_depth(publisher.depth());
+            publisher.publish(bids, asks, now);
+        }
+        if !self.observers.is_empty() {
+            let top_of_book = self.get_top_of_book();
+            if top_of_book != self.top_of_book {
+                self.top_of_book = top_of_book;
+                self.notify(MarketEvent::TopOfBookChanged { top_of_book, timestamp: now });
+            }
+        }
+    }
+
+    pub fn get_top_of_book(&self) -> TopOfBook {
+        let (bids, asks) = self.order_book.get_market_depth(1);
+        TopOfBook {
+            bid: bids.first().copied(),
+            ask: asks.first().copied(),
+        }
+    }
+
+    // Observers are called in the order they were added
+    pub fn add_observer<O: MarketObserver + 'static>(&mut self, observer: O) {
+        if self.observers.is_empty() {
+            self.top_of_book = self.get_top_of_book();
+        }
+        self.observers.push(Box::new(observer));
+    }
+
+    fn notify(&mut self, event: MarketEvent) {
+        for observer in &mut self.observers {
+            observer.on_event(&event);
+        }
+    }
+
+    // Tells observers what `reports` say happened, in the same order. Each trade
+    // goes out at the first of its two fill reports, and the trade that tripped the
+    // circuit breaker is followed straight away by the halt.
+    fn notify_reports(&mut self, reports: &[ExecutionReport], trades: &[Trade], breach: Option<&Breach>) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let mut trades = trades.iter().peekable();
+        for report in reports {
+            let event = match report.execution_type {
+                ExecutionType::New | ExecutionType::Replaced | ExecutionType::Queued => {
+                    MarketEvent::OrderAccepted(report.clone())
+                }
+                ExecutionType::Rejected => MarketEvent::OrderRejected(report.clone()),
+                ExecutionType::Cancelled | ExecutionType::Expired => MarketEvent::OrderCancelled(report.clone()),
+                // A self-trade decrement that leaves the order working is not a cancel
+                ExecutionType::SelfTradePrevented if report.status == OrderStatus::Cancelled => {
+                    MarketEvent::OrderCancelled(report.clone())
+                }
+                ExecutionType::SelfTradePrevented => continue,
+                ExecutionType::PartialFill | ExecutionType::Fill => {
+                    let Some(trade) = trades.next_if(|trade| Some(trade.id) == report.trade_id) else {
+                        continue;
+                    };
+                    self.notify(MarketEvent::Traded(trade.clone()));
+                    if breach.is_some_and(|breach| breach.trade_id == trade.id) {
+                        self.notify(MarketEvent::PhaseChanged {
+                            from: TradingPhase::Continuous,
+                            to: TradingPhase::Halted,
+                            timestamp: trade.timestamp,
+                        });
+                    }
+                    continue;
+                }
+            };
+            self.notify(event);
+        }
+    }
+
+    // Publishes level 2 market data from now on, starting from the book as it
+    // stands; `None` stops publishing and disconnects every subscriber
+    pub fn set_market_data(&mut self, publisher: Option<L2Publisher>) {
+        self.market_data = publisher;
+        self.publish_market_data(self.clock.now());
+    }
+
+    pub fn market_data(&self) -> Option<&L2Publisher> {
+        self.market_data.as_ref()
+    }
+
+    // A new level 2 stream, or `None` if the simulator publishes no market data
+    pub fn subscribe_market_data(&mut self) -> Option<Receiver<L2Message>> {
+        Some(self.market_data.as_mut()?.subscribe())
+    }
+
+    // The book's level 3 stream of every resting order event
+    pub fn subscribe_order_events(&mut self) -> Receiver<L3Message> {
+        self.order_book.subscribe_order_events()
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        &self.order_book
+    }
+
+    // Charges fees on every fill and settles it against both counterparties'
+    // accounts before adding it to the tape. Returns the trades as recorded when
+    // there are observers to tell about them.
+    fn record_trades(&mut self, new_trades: Vec<Trade>) -> Vec<Trade> {
+        let mut recorded = Vec::new();
+        for mut trade in new_trades {
+            self.charge_fees(&mut trade);
+            self.accounts.apply_trade(&trade);
+            self.tape_hash = hash_trade(self.tape_hash, &trade);
+            if !self.observers.is_empty() {
+                recorded.push(trade.clone());
+            }
+            self.trades.push(trade);
+        }
+        recorded
+    }
+
+    // Orders that left the book go to the archive when closed orders are evicted;
+    // otherwise they are dropped as before
+    fn archive_orders(&mut self, orders: Vec<Order>) {
+        if !self.retention.evict_closed_orders {
+            return;
+        }
+        if let Some(archive) = &mut self.order_archive {
+            for order in orders {
+                archive.archive(order);
+            }
+        }
+    }
+
+    // Each leg's tier is set by what its account traded this month before this fill
+    fn charge_fees(&mut self, trade: &mut Trade) {
+        let buy_volume = self.accounts.monthly_volume(trade.buy_account_id, trade.timestamp);
+        let sell_volume = self.accounts.monthly_volume(trade.sell_account_id, trade.timestamp);
+        trade.buy_fee = self.fee_schedule.fee(trade.buy_liquidity, trade.price, trade.quantity, buy_volume);
+        trade.sell_fee = self.fee_schedule.fee(trade.sell_liquidity, trade.price, trade.quantity, sell_volume);
+        self.fees_collected += trade.buy_fee + trade.sell_fee;
+    }
+
+    pub fn set_fee_schedule(&mut self, fee_schedule: FeeSchedule) {
+        self.fee_schedule = fee_schedule;
+    }
+
+    pub fn fee_schedule(&self) -> &FeeSchedule {
+        &self.fee_schedule
+    }
+
+    // Sets how orders resting at the same price share incoming quantity
+    pub fn set_matching_policy<P: MatchingPolicy + 'static>(&mut self, policy: P) {
+        self.order_book.set_matching_policy(policy);
+    }
+
+    pub fn matching_policy(&self) -> &dyn MatchingPolicy {
+        self.order_book.matching_policy()
+    }
+
+    // Fees charged minus rebates paid, in price units
+    pub fn get_fees_collected(&self) -> Cash {
+        self.fees_collected
+    }
+
+    pub fn deposit(&mut self, account_id: AccountId, cash: f64) -> Result<(), MarketError> {
+        let cash = (cash * self.instrument.price_scale as f64).round() as Cash;
+        self.record(self.clock.now(), || Command::Deposit { account_id, cash })?;
+        self.accounts.deposit(account_id, cash);
+        Ok(())
+    }
+
+    pub fn get_account(&self, account_id: AccountId) -> Option<&Account> {
+        self.accounts.get_account(account_id)
+    }
+
+    pub fn get_accounts(&self) -> &AccountLedger {
+        &self.accounts
+    }
+
+    // Cash, position and PnL are in fixed-point price units; convert them with
+    // `instrument().to_f64`. Open positions are marked to the last trade price.
+    pub fn get_unrealized_pnl(&self, account_id: AccountId) -> Option<Cash> {
+        let account = self.accounts.get_account(account_id)?;
+        Some(match self.get_last_trade_price() {
+            Some(mark_price) => account.unrealized_pnl(mark_price),
+            None => 0,
+        })
     }
 
     pub fn get_order(&self, order_id: &OrderId) -> Option<&Order> {
         self.order_book.get_order(order_id)
     }
 
+    // Looks an order up in the book and then in the order archive
+    pub fn find_order(&self, order_id: &OrderId) -> Option<Order> {
+        match self.get_order(order_id) {
+            Some(order) => Some(order.clone()),
+            None => self.order_archive.as_ref()?.get(order_id),
+        }
+    }
+
+    pub fn retention_policy(&self) -> &RetentionPolicy {
+        &self.retention
+    }
+
+    // Takes effect at once: a tape over the new capacity is trimmed now. With
+    // `evict_closed_orders`, each order is evicted to the order archive as it
+    // closes and `find_order` falls back to the archive; orders that closed
+    // before stay on record in the book. Setting a policy clears a spill error.
+    pub fn set_retention_policy(&mut self, retention: RetentionPolicy) -> Result<(), MarketError> {
+        self.trades
+            .configure(retention.trade_capacity, retention.trade_spill_path.as_deref())?;
+        self.order_book.set_evict_closed_orders(retention.evict_closed_orders);
+        self.retention = retention;
+        Ok(())
+    }
+
+    pub fn set_order_archive<A: OrderArchive + 'static>(&mut self, archive: A) {
+        self.order_archive = Some(Box::new(archive));
+    }
+
+    pub fn order_archive(&self) -> Option<&dyn OrderArchive> {
+        self.order_archive.as_deref()
+    }
+
+    // Orders on record in the book, working or closed but not evicted
+    pub fn get_order_count(&self) -> usize {
+        self.order_book.order_count()
+    }
+
+    // Records every command from now on; `None` stops journaling
+    pub fn set_journal(&mut self, journal: Option<Journal>) {
+        self.journal = journal;
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    // Writes the command to the journal, if there is one, before it is applied; a
+    // command the journal cannot take is refused, so the journal never misses one.
+    // Once the trade tape has failed to spill, every command is refused until
+    // `set_retention_policy` sets it up again.
+    fn record(&mut self, at: DateTime<Utc>, command: impl FnOnce() -> Command) -> Result<(), MarketError> {
+        if let Some(error) = self.trades.error() {
+            return Err(MarketError::Storage(error.to_string()));
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.record(at, command())?;
+        }
+        Ok(())
+    }
+
+    // Applies journaled commands in sequence order, starting from the first, as
+    // they were applied live and at the times they were journaled, whatever this
+    // simulator's clock says. Replay onto a simulator set up like the original:
+    // same instrument, spread, fees, risk checks and policies. Commands that failed
+    // live fail again and are skipped. Each command is journaled again if this
+    // simulator has a journal.
+    pub fn replay(&mut self, entries: impl IntoIterator<Item = JournalEntry>) -> Result<(), MarketError> {
+        for (expected, entry) in (1..).zip(entries) {
+            if entry.sequence != expected {
+                return Err(MarketError::JournalGap { expected, found: entry.sequence });
+            }
+            self.record(entry.timestamp, || entry.command.clone())?;
+            self.apply(entry.command, entry.timestamp);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, command: Command, at: DateTime<Utc>) {
+        match command {
+            Command::Submit(order) => {
+                let _ = self.submit(order);
+            }
+            Command::Cancel(order_id) => {
+                let _ = self.cancel(order_id, at);
+            }
+            Command::Amend { order_id, price, quantity } => {
+                let _ = self.amend(order_id, price, quantity, at);
+            }
+            Command::SetPhase(phase) => {
+                let _ = self.change_phase(phase, at);
+            }
+            Command::Resume => {
+                if self.phase == TradingPhase::Halted {
+                    self.reopen(at);
+                }
+            }
+            Command::Update(now) => {
+                self.advance(now);
+            }
+            Command::ExpireOrders(now) => {
+                self.expire(now);
+            }
+            Command::EndOfDay => {
+                self.close_day(at);
+            }
+            Command::Deposit { account_id, cash } => self.accounts.deposit(account_id, cash),
+        }
+    }
+
+    // Everything needed to carry on from this point, except the pluggable parts
+    // listed on MarketSnapshot
+    pub fn snapshot(&self) -> MarketSnapshot {
+        MarketSnapshot {
+            minimum_spread_percentage: self.minimum_spread_percentage,
+            instrument: self.instrument,
+            fee_schedule: self.fee_schedule.clone(),
+            self_trade_prevention: self.self_trade_prevention.clone(),
+            halt_policy: self.halt_policy.clone(),
+            retention: self.retention.clone(),
+            book: self.order_book.snapshot(),
+            trades: self.trades.recent().iter().cloned().collect(),
+            evicted_trades: self.trades.evicted(),
+            signed_volume: self.trades.signed_volume(),
+            tape_hash: self.tape_hash,
+            accounts: self.accounts.clone(),
+            fees_collected: self.fees_collected,
+            phase: self.phase,
+            halt: self.halt,
+            queued_orders: self.queued_orders.clone(),
+            price_window: self.order_book.price_window().clone(),
+            auction_ends_at: self.auction_ends_at,
+            id_position: self.order_book.id_generator().position(),
+        }
+    }
+
+    // Puts the simulator back in the saved state. Its matching policy, clock, ID
+    // generator, risk checks, order archive and journal are kept; the journal is
+    // not written to. The ID generator carries on from the saved position.
+    pub fn restore(&mut self, snapshot: MarketSnapshot) -> Result<(), MarketError> {
+        // The tape is the only part that can fail, on its spill file, so it is
+        // rebuilt first
+        let mut trades = TradeTape::new();
+        trades.configure(snapshot.retention.trade_capacity, snapshot.retention.trade_spill_path.as_deref())?;
+        trades.restore(snapshot.trades, snapshot.evicted_trades, snapshot.signed_volume)?;
+        self.trades = trades;
+        self.order_book.set_evict_closed_orders(snapshot.retention.evict_closed_orders);
+        self.retention = snapshot.retention;
+        self.order_book.restore(snapshot.book);
+        let now = self.clock.now();
+        self.publish_market_data(now);
+        self.minimum_spread_percentage = snapshot.minimum_spread_percentage;
+        self.instrument = snapshot.instrument;
+        self.fee_schedule = snapshot.fee_schedule;
+        self.self_trade_prevention = snapshot.self_trade_prevention;
+        self.order_book.set_circuit_breaker(snapshot.halt_policy.circuit_breaker);
+        self.halt_policy = snapshot.halt_policy;
+        self.tape_hash = snapshot.tape_hash;
+        self.accounts = snapshot.accounts;
+        self.fees_collected = snapshot.fees_collected;
+        self.phase = snapshot.phase;
+        self.halt = snapshot.halt;
+        self.queued_orders = snapshot.queued_orders;
+        self.order_book.set_price_window(snapshot.price_window);
+        self.auction_ends_at = snapshot.auction_ends_at;
+        if let Some(position) = snapshot.id_position {
+            self.order_book.id_generator().resume_from(position);
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MarketError> {
+        snapshot::save(path, &self.snapshot())
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), MarketError> {
+        self.restore(snapshot::load(path)?)
+    }
+
+    // Fingerprint of everything that decides what the market does next: the
+    // book, every trade recorded, accounts, fees, phase, halt and queued orders.
+    // Two simulators fed the same journal have the same hash. Trade IDs are left
+    // out, as they come from each simulator's ID generator.
+    pub fn state_hash(&self) -> u64 {
+        let mut state = DefaultHasher::new();
+        self.order_book.hash_state(&mut state);
+        self.tape_hash.hash(&mut state);
+        for account in self.accounts.accounts() {
+            account.hash(&mut state);
+        }
+        self.fees_collected.hash(&mut state);
+        self.phase.hash(&mut state);
+        self.halt.map(|halt| halt.reason).hash(&mut state);
+        self.queued_orders.hash(&mut state);
+        state.finish()
+    }
+
     pub fn get_best_bid(&self) -> Option<Price> {
         self.order_book.get_best_bid()
     }
@@ -91,7 +1059,7 @@ impl MarketSimulator {
         self.order_book.get_best_ask()
     }
 
-    pub fn get_spread(&self) -> Option<f64> {
+    pub fn get_spread(&self) -> Option<Price> {
         self.order_book.get_spread()
     }
 
@@ -99,27 +1067,98 @@ impl MarketSimulator {
         self.order_book.get_spread_percentage()
     }
 
-    pub fn get_market_depth(&self, levels: usize) -> (Vec<(Price, Quantity)>, Vec<(Price, Quantity)>) {
+    pub fn get_market_depth(&self, levels: usize) -> (DepthLevels, DepthLevels) {
         self.order_book.get_market_depth(levels)
     }
 
+    // The same prices as decimals, converted with the instrument's price scale
+    pub fn get_best_bid_f64(&self) -> Option<f64> {
+        self.get_best_bid().map(|price| self.instrument.to_f64(price))
+    }
+
+    pub fn get_best_ask_f64(&self) -> Option<f64> {
+        self.get_best_ask().map(|price| self.instrument.to_f64(price))
+    }
+
+    pub fn get_spread_f64(&self) -> Option<f64> {
+        self.get_spread().map(|spread| self.instrument.to_f64(spread))
+    }
+
+    pub fn get_last_trade_price_f64(&self) -> Option<f64> {
+        self.get_last_trade_price().map(|price| self.instrument.to_f64(price))
+    }
+
     pub fn get_recent_trades(&self, count: usize) -> Vec<&Trade> {
-        self.trades.iter().rev().take(count).collect()
+        self.trades.recent().iter().rev().take(count).collect()
     }
 
-    pub fn get_all_trades(&self) -> &Vec<Trade> {
-        &self.trades
+    // The trades held in memory, oldest first; everything unless the retention
+    // policy caps the tape
+    pub fn get_all_trades(&self) -> &VecDeque<Trade> {
+        self.trades.recent()
+    }
+
+    // Every trade since the start, reading back any that were spilled to disk.
+    // Trades evicted without a spill file are missing.
+    pub fn get_trade_history(&self) -> Result<Vec<Trade>, MarketError> {
+        Ok(self.trades.history()?)
+    }
+
+    // Trades recorded since the start, in memory or not
+    pub fn get_trade_count(&self) -> usize {
+        self.trades.total()
+    }
+
+    // Buyer-initiated minus seller-initiated volume over the whole tape
+    pub fn get_signed_volume(&self) -> i64 {
+        self.trades.signed_volume()
+    }
+
+    // Signed volume of the last `count` trades
+    pub fn get_recent_signed_volume(&self, count: usize) -> i64 {
+        self.trades.recent().iter().rev().take(count).map(Trade::signed_quantity).sum()
+    }
+
+    // Signed volume of the last `count` trades as a fraction of their total volume,
+    // from -1.0 (all selling) to 1.0 (all buying)
+    pub fn get_order_flow_imbalance(&self, count: usize) -> Option<f64> {
+        let volume: Quantity = self.trades.recent().iter().rev().take(count).map(|t| t.quantity).sum();
+        (volume > 0).then(|| self.get_recent_signed_volume(count) as f64 / volume as f64)
     }
 
     pub fn print_market_status(&self) {
         println!("\n=== MARKET STATUS ===");
+        println!("Phase: {}", self.phase);
+        if let Some(halt) = &self.halt {
+            let reason = match halt.reason {
+                HaltReason::Manual => "manual".to_string(),
+                HaltReason::CircuitBreaker { trade_price, reference_price } => format!(
+                    "circuit breaker, ${} against ${}",
+                    self.instrument.format_price(trade_price),
+                    self.instrument.format_price(reference_price)
+                ),
+            };
+            println!("Halted ({}) until {}, {} orders queued", reason, halt.resumes_at.format("%H:%M:%S"), self.queued_orders.len());
+        }
+
+        if self.phase.is_auction() {
+            match self.get_indicative_uncross() {
+                Some(uncross) => println!(
+                    "Indicative Price: ${}, Volume: {}, Imbalance: {}",
+                    self.instrument.format_price(uncross.price),
+                    uncross.volume,
+                    uncross.imbalance
+                ),
+                None => println!("Indicative Price: none (book does not cross)"),
+            }
+        }
         
         if let (Some(bid), Some(ask)) = (self.get_best_bid(), self.get_best_ask()) {
-            println!("Best Bid: ${:.2}", bid.into_inner());
-            println!("Best Ask: ${:.2}", ask.into_inner());
+            println!("Best Bid: ${}", self.instrument.format_price(bid));
+            println!("Best Ask: ${}", self.instrument.format_price(ask));
             
-            if let Some(spread) = self.get_spread() {
-                println!("Spread: ${:.2}", spread);
+            if let Some(spread) = self.order_book.get_spread() {
+                println!("Spread: ${}", self.instrument.format_price(spread));
             }
             
             if let Some(spread_pct) = self.get_spread_percentage() {
@@ -138,13 +1177,13 @@ impl MarketSimulator {
         let max_levels = bids.len().max(asks.len());
         for i in 0..max_levels {
             let bid_str = if i < bids.len() {
-                format!("{:.2}\t{}", bids[i].0.into_inner(), bids[i].1)
+                format!("{}\t{}", self.instrument.format_price(bids[i].0), bids[i].1)
             } else {
                 "\t".to_string()
             };
             
             let ask_str = if i < asks.len() {
-                format!("{:.2}\t{}", asks[i].0.into_inner(), asks[i].1)
+                format!("{}\t{}", self.instrument.format_price(asks[i].0), asks[i].1)
             } else {
                 "".to_string()
             };
@@ -152,13 +1191,20 @@ impl MarketSimulator {
             println!("{}\t\t{}", bid_str, ask_str);
         }
 
+        println!("\nFees Collected: ${}", self.instrument.format_price(self.fees_collected));
+
         let recent_trades = self.get_recent_trades(3);
         if !recent_trades.is_empty() {
             println!("\nRecent Trades:");
             for trade in recent_trades {
-                println!("Price: ${:.2}, Quantity: {}, Time: {}", 
-                    trade.price.into_inner(), 
+                println!("Price: ${}, Quantity: {}, Aggressor: {}, Time: {}", 
+                    self.instrument.format_price(trade.price), 
                     trade.quantity, 
+                    match trade.aggressor_side {
+                        Some(OrderSide::Buy) => "Buy",
+                        Some(OrderSide::Sell) => "Sell",
+                        None => "Auction",
+                    },
                     trade.timestamp.format("%H:%M:%S"));
             }
         }
@@ -166,3 +1212,36 @@ impl MarketSimulator {
         println!("====================\n");
     }
 }
+
+// Trades happen at limit prices, so an order whose limit price times quantity
+// fits in Cash can never produce a fill value that does not
+fn validate_notional(price: Price, quantity: Quantity) -> Result<(), MarketError> {
+    match Cash::try_from(quantity).ok().and_then(|quantity| price.checked_mul(quantity)) {
+        Some(_) => Ok(()),
+        None => Err(MarketError::NotionalOverflow { price, quantity }),
+    }
+}
+
+// Spread between a bid and an ask as a percentage of their mid price. Prices are
+// exact integers, so only this final ratio is done in floating point.
+fn spread_percentage(bid: Price, ask: Price) -> f64 {
+    let mid_price = (bid + ask) as f64 / 2.0;
+    (ask - bid) as f64 / mid_price * 100.0
+}
+
+// Chains one trade onto the running hash of the tape
+fn hash_trade(tape_hash: u64, trade: &Trade) -> u64 {
+    let mut state = DefaultHasher::new();
+    tape_hash.hash(&mut state);
+    (trade.buy_order_id, trade.sell_order_id, trade.buy_account_id, trade.sell_account_id).hash(&mut state);
+    (trade.price, trade.quantity, trade.aggressor_side, trade.buy_fee, trade.sell_fee).hash(&mut state);
+    trade.timestamp.hash(&mut state);
+    state.finish()
+}
+
+fn expiry_reports(expired: &[Order], now: DateTime<Utc>) -> Vec<ExecutionReport> {
+    expired
+        .iter()
+        .map(|order| ExecutionReport::new(order, ExecutionType::Expired, now))
+        .collect()
+}
@@ -0,0 +1,506 @@
+use crate::error::MarketError;
+use crate::order_book::{DepthLevels, Order, OrderBook, OrderId, OrderSide, Price, Quantity};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelAction {
+    // A price that was not among the published levels now is
+    Add,
+    // Same price, new total quantity
+    Update,
+    // The price has left the published levels; its quantity is zero
+    Delete,
+}
+
+// One change to an aggregated price level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelDelta {
+    // Starts at 1 and goes up by one per delta
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub side: OrderSide,
+    pub price: Price,
+    // Total visible quantity at the price after the change
+    pub quantity: Quantity,
+    pub action: LevelAction,
+}
+
+// The published levels as of `sequence`, best price first. Snapshots do not take
+// a sequence number of their own: the next delta is `sequence + 1`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthSnapshot {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub bids: DepthLevels,
+    pub asks: DepthLevels,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum L2Message {
+    Delta(LevelDelta),
+    Snapshot(DepthSnapshot),
+}
+
+impl L2Message {
+    pub fn sequence(&self) -> u64 {
+        match self {
+            L2Message::Delta(delta) => delta.sequence,
+            L2Message::Snapshot(snapshot) => snapshot.sequence,
+        }
+    }
+}
+
+// Publishes the top `depth` levels of each side of a book. After every change to
+// the book the levels are compared with those last published and the
+// differences go out as deltas to every subscriber, so one request that moves
+// several levels sends a delta per level it moved, in one batch.
+#[derive(Debug)]
+pub struct L2Publisher {
+    depth: usize,
+    // Deltas between full snapshots, which go out at the end of the change that
+    // reaches the interval; none but the one each subscriber starts with when `None`
+    snapshot_interval: Option<u64>,
+    sequence: u64,
+    deltas_since_snapshot: u64,
+    bids: DepthLevels,
+    asks: DepthLevels,
+    timestamp: DateTime<Utc>,
+    subscribers: Vec<Sender<L2Message>>,
+}
+
+impl L2Publisher {
+    pub fn new(depth: usize, snapshot_interval: Option<u64>) -> Self {
+        Self {
+            depth,
+            snapshot_interval,
+            sequence: 0,
+            deltas_since_snapshot: 0,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            timestamp: DateTime::UNIX_EPOCH,
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Sequence number of the last delta sent
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    // A stream that starts with a snapshot of the levels as last published.
+    // Messages queue up until they are read; dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<L2Message> {
+        let (sender, receiver) = mpsc::channel();
+        // The receiver is still held, so this cannot fail
+        let _ = sender.send(L2Message::Snapshot(self.snapshot()));
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn snapshot(&self) -> DepthSnapshot {
+        DepthSnapshot {
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
+        }
+    }
+
+    // Sends the changes from the levels last published to `bids` and `asks`, which
+    // should be the book's top `depth` levels at `timestamp`
+    pub fn publish(&mut self, bids: DepthLevels, asks: DepthLevels, timestamp: DateTime<Utc>) {
+        self.timestamp = timestamp;
+        let mut changes = level_changes(OrderSide::Buy, &self.bids, &bids);
+        changes.extend(level_changes(OrderSide::Sell, &self.asks, &asks));
+        (self.bids, self.asks) = (bids, asks);
+
+        for (side, price, quantity, action) in changes {
+            self.sequence += 1;
+            self.send(L2Message::Delta(LevelDelta {
+                sequence: self.sequence,
+                timestamp,
+                side,
+                price,
+                quantity,
+                action,
+            }));
+            self.deltas_since_snapshot += 1;
+        }
+        // Only after the whole batch, so the snapshot's sequence is that of the
+        // last delta its levels include
+        if self.snapshot_interval.is_some_and(|interval| self.deltas_since_snapshot >= interval) {
+            self.send(L2Message::Snapshot(self.snapshot()));
+            self.deltas_since_snapshot = 0;
+        }
+    }
+
+    fn send(&mut self, message: L2Message) {
+        self.subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+}
+
+// Deletes first, then adds and updates from the best price outwards
+fn level_changes(
+    side: OrderSide,
+    before: &DepthLevels,
+    after: &DepthLevels,
+) -> Vec<(OrderSide, Price, Quantity, LevelAction)> {
+    let previous: BTreeMap<Price, Quantity> = before.iter().copied().collect();
+    let current: BTreeMap<Price, Quantity> = after.iter().copied().collect();
+
+    let deletes = before
+        .iter()
+        .filter(|(price, _)| !current.contains_key(price))
+        .map(|&(price, _)| (side, price, 0, LevelAction::Delete));
+    let upserts = after.iter().filter_map(|&(price, quantity)| match previous.get(&price) {
+        None => Some((side, price, quantity, LevelAction::Add)),
+        Some(&old) if old != quantity => Some((side, price, quantity, LevelAction::Update)),
+        Some(_) => None,
+    });
+    deletes.chain(upserts).collect()
+}
+
+// A subscriber's copy of the published levels, rebuilt from an L2 stream
+#[derive(Debug, Clone, Default)]
+pub struct L2Book {
+    // Last sequence applied; `None` until the first snapshot and after a gap
+    sequence: Option<u64>,
+    bids: BTreeMap<Price, Quantity>,
+    asks: BTreeMap<Price, Quantity>,
+}
+
+impl L2Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Applies the next message. A delta that skips a sequence number empties the
+    // book and returns `MarketError::FeedGap`; deltas are then ignored until the
+    // next snapshot brings the book back in sync. Deltas a snapshot already
+    // covered are ignored too.
+    pub fn apply(&mut self, message: &L2Message) -> Result<(), MarketError> {
+        let delta = match message {
+            L2Message::Snapshot(snapshot) => {
+                self.bids = snapshot.bids.iter().copied().collect();
+                self.asks = snapshot.asks.iter().copied().collect();
+                self.sequence = Some(snapshot.sequence);
+                return Ok(());
+            }
+            L2Message::Delta(delta) => delta,
+        };
+
+        let Some(last) = self.sequence else {
+            return Ok(());
+        };
+        if delta.sequence <= last {
+            return Ok(());
+        }
+        if delta.sequence != last + 1 {
+            *self = Self::new();
+            return Err(MarketError::FeedGap {
+                expected: last + 1,
+                found: delta.sequence,
+            });
+        }
+
+        let levels = match delta.side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        };
+        match delta.action {
+            LevelAction::Add | LevelAction::Update => levels.insert(delta.price, delta.quantity),
+            LevelAction::Delete => levels.remove(&delta.price),
+        };
+        self.sequence = Some(delta.sequence);
+        Ok(())
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    // Best bids and asks first, like MarketSimulator::get_market_depth
+    pub fn get_market_depth(&self, levels: usize) -> (DepthLevels, DepthLevels) {
+        (
+            self.bids.iter().rev().take(levels).map(|(&p, &q)| (p, q)).collect(),
+            self.asks.iter().take(levels).map(|(&p, &q)| (p, q)).collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderEventKind {
+    // The order joined the back of the queue at its price showing `quantity`.
+    // An iceberg shows each refreshed slice with another Add.
+    Add,
+    // `quantity` of the order traded; once nothing is left on show the order is
+    // off the book
+    Execute,
+    // `quantity` was taken off the order's displayed size without trading
+    Reduce,
+    // The order left the book without trading the `quantity` it showed
+    Delete,
+}
+
+// One change to one resting order. Only the displayed size of an order is ever
+// published, and stops are not in the book until they trigger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    // Starts at 1 and goes up by one per event
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub kind: OrderEventKind,
+    pub order_id: OrderId,
+    pub side: OrderSide,
+    // The order's limit price, which an auction may trade it away from
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestingOrder {
+    pub order_id: OrderId,
+    pub price: Price,
+    pub quantity: Quantity,
+}
+
+// Every resting order as of `sequence`, best price first and in time priority
+// within a price. The next event is `sequence + 1`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct L3Snapshot {
+    pub sequence: u64,
+    pub bids: Vec<RestingOrder>,
+    pub asks: Vec<RestingOrder>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum L3Message {
+    Event(OrderEvent),
+    Snapshot(L3Snapshot),
+}
+
+// The order-by-order stream of one OrderBook, which calls it for every change to
+// a resting order. Events are numbered whether or not anyone is subscribed.
+#[derive(Debug, Default)]
+pub struct L3Publisher {
+    sequence: u64,
+    subscribers: Vec<Sender<L3Message>>,
+}
+
+// A cloned book is a separate book: it keeps numbering from the same place but
+// publishes to nobody until subscribed to
+impl Clone for L3Publisher {
+    fn clone(&self) -> Self {
+        Self {
+            sequence: self.sequence,
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl L3Publisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sequence number of the last event
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    // A stream that starts with a snapshot of the given resting orders, which
+    // should be the book's as of the last event
+    pub fn subscribe(&mut self, bids: Vec<RestingOrder>, asks: Vec<RestingOrder>) -> Receiver<L3Message> {
+        let (sender, receiver) = mpsc::channel();
+        // The receiver is still held, so this cannot fail
+        let _ = sender.send(L3Message::Snapshot(L3Snapshot {
+            sequence: self.sequence,
+            bids,
+            asks,
+        }));
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // Sends every subscriber a fresh snapshot, for when the book has been
+    // replaced wholesale rather than changed order by order
+    pub fn resync(&mut self, bids: Vec<RestingOrder>, asks: Vec<RestingOrder>) {
+        self.send(L3Message::Snapshot(L3Snapshot {
+            sequence: self.sequence,
+            bids,
+            asks,
+        }));
+    }
+
+    pub fn publish(&mut self, kind: OrderEventKind, order: &Order, quantity: Quantity, timestamp: DateTime<Utc>) {
+        self.sequence += 1;
+        if self.subscribers.is_empty() {
+            return;
+        }
+        self.send(L3Message::Event(OrderEvent {
+            sequence: self.sequence,
+            timestamp,
+            kind,
+            order_id: order.id,
+            side: order.side,
+            price: order.price,
+            quantity,
+        }));
+    }
+
+    fn send(&mut self, message: L3Message) {
+        self.subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+}
+
+// A subscriber's full-depth copy of a book, rebuilt from an L3 stream
+#[derive(Debug, Clone, Default)]
+pub struct L3Book {
+    // Last sequence applied; `None` until the first snapshot and after a gap
+    sequence: Option<u64>,
+    // Displayed size of each resting order, and its side and price
+    orders: HashMap<OrderId, (OrderSide, Price, Quantity)>,
+    // Order IDs at each price in time priority
+    bids: BTreeMap<Price, Vec<OrderId>>,
+    asks: BTreeMap<Price, Vec<OrderId>>,
+}
+
+impl L3Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Applies the next message, with the same gap handling as L2Book::apply. An
+    // event for an order the book does not hold returns
+    // `MarketError::UnknownOrder`.
+    pub fn apply(&mut self, message: &L3Message) -> Result<(), MarketError> {
+        let event = match message {
+            L3Message::Snapshot(snapshot) => {
+                *self = Self::new();
+                for (side, orders) in [(OrderSide::Buy, &snapshot.bids), (OrderSide::Sell, &snapshot.asks)] {
+                    for order in orders {
+                        self.add(order.order_id, side, order.price, order.quantity);
+                    }
+                }
+                self.sequence = Some(snapshot.sequence);
+                return Ok(());
+            }
+            L3Message::Event(event) => event,
+        };
+
+        let Some(last) = self.sequence else {
+            return Ok(());
+        };
+        if event.sequence <= last {
+            return Ok(());
+        }
+        if event.sequence != last + 1 {
+            *self = Self::new();
+            return Err(MarketError::FeedGap {
+                expected: last + 1,
+                found: event.sequence,
+            });
+        }
+        self.sequence = Some(event.sequence);
+
+        if event.kind == OrderEventKind::Add {
+            self.add(event.order_id, event.side, event.price, event.quantity);
+            return Ok(());
+        }
+        let Some((_, _, displayed)) = self.orders.get_mut(&event.order_id) else {
+            return Err(MarketError::UnknownOrder(event.order_id));
+        };
+        *displayed = displayed.saturating_sub(event.quantity);
+        if event.kind == OrderEventKind::Delete || *displayed == 0 {
+            self.remove(event.order_id);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, order_id: OrderId, side: OrderSide, price: Price, quantity: Quantity) {
+        self.orders.insert(order_id, (side, price, quantity));
+        self.levels_mut(side).entry(price).or_default().push(order_id);
+    }
+
+    fn remove(&mut self, order_id: OrderId) {
+        let Some((side, price, _)) = self.orders.remove(&order_id) else {
+            return;
+        };
+        let levels = self.levels_mut(side);
+        if let Some(level) = levels.get_mut(&price) {
+            level.retain(|id| *id != order_id);
+            if level.is_empty() {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    fn levels_mut(&mut self, side: OrderSide) -> &mut BTreeMap<Price, Vec<OrderId>> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    // Resting orders on one side, best price first and in time priority within a
+    // price, like OrderBook::resting_orders
+    pub fn resting_orders(&self, side: OrderSide) -> Vec<RestingOrder> {
+        let levels: Box<dyn Iterator<Item = (&Price, &Vec<OrderId>)>> = match side {
+            OrderSide::Buy => Box::new(self.bids.iter().rev()),
+            OrderSide::Sell => Box::new(self.asks.iter()),
+        };
+        levels
+            .flat_map(|(&price, level)| {
+                level.iter().map(move |&order_id| RestingOrder {
+                    order_id,
+                    price,
+                    quantity: self.orders[&order_id].2,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_market_depth(&self, levels: usize) -> (DepthLevels, DepthLevels) {
+        let depth = |side| -> DepthLevels {
+            let mut depth: DepthLevels = Vec::new();
+            for order in self.resting_orders(side) {
+                if depth.last().is_some_and(|&(price, _)| price == order.price) {
+                    depth.last_mut().unwrap().1 += order.quantity;
+                } else if depth.len() == levels {
+                    break;
+                } else {
+                    depth.push((order.price, order.quantity));
+                }
+            }
+            depth
+        };
+        (depth(OrderSide::Buy), depth(OrderSide::Sell))
+    }
+
+    // Whether this copy holds exactly the orders resting in `book`, in the same
+    // queue positions and showing the same sizes
+    pub fn matches(&self, book: &OrderBook) -> bool {
+        [OrderSide::Buy, OrderSide::Sell]
+            .into_iter()
+            .all(|side| self.resting_orders(side) == book.resting_orders(side))
+    }
+}
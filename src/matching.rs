@@ -0,0 +1,117 @@
+use crate::order_book::Quantity;
+use std::fmt;
+
+// Splits an incoming order's quantity among the resting orders at one price
+// level. `resting` holds the visible quantity of each order that can trade, in
+// time priority. Allocations should add up to the smaller of `quantity` and the
+// level's total; anything a policy leaves out is offered again in another round.
+pub trait MatchingPolicy: fmt::Debug {
+    fn allocate(&self, quantity: Quantity, resting: &[Quantity]) -> Vec<Quantity>;
+
+    // Whether orders behind those that cover the incoming quantity in time priority
+    // can change the allocation. When they cannot, the book stops reading the
+    // level early.
+    fn uses_whole_level(&self) -> bool {
+        true
+    }
+}
+
+// Price-time priority: earlier orders are filled completely before later ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fifo;
+
+impl MatchingPolicy for Fifo {
+    fn allocate(&self, quantity: Quantity, resting: &[Quantity]) -> Vec<Quantity> {
+        let mut remaining = quantity;
+        resting
+            .iter()
+            .map(|&size| {
+                let allocation = size.min(remaining);
+                remaining -= allocation;
+                allocation
+            })
+            .collect()
+    }
+
+    fn uses_whole_level(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProRataRounding {
+    #[default]
+    Down,
+    Nearest,
+}
+
+// Each order gets a share in proportion to its size. Shares are rounded to whole
+// lots and shares below `minimum_allocation` are dropped; whatever that leaves
+// over goes out in time priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProRata {
+    pub minimum_allocation: Quantity,
+    pub rounding: ProRataRounding,
+}
+
+impl MatchingPolicy for ProRata {
+    fn allocate(&self, quantity: Quantity, resting: &[Quantity]) -> Vec<Quantity> {
+        let total: Quantity = resting.iter().sum();
+        if quantity >= total {
+            return resting.to_vec();
+        }
+
+        let mut remaining = quantity;
+        let mut allocations: Vec<Quantity> = resting
+            .iter()
+            .map(|&size| {
+                let numerator = quantity as u128 * size as u128;
+                let share = match self.rounding {
+                    ProRataRounding::Down => numerator / total as u128,
+                    ProRataRounding::Nearest => (numerator + total as u128 / 2) / total as u128,
+                } as Quantity;
+                // Rounding up can overshoot the incoming quantity, so cap it
+                let allocation = share.min(size).min(remaining);
+                let allocation = if allocation < self.minimum_allocation { 0 } else { allocation };
+                remaining -= allocation;
+                allocation
+            })
+            .collect();
+
+        // Whatever rounding and the minimum left over goes out in time priority
+        for (allocation, &size) in allocations.iter_mut().zip(resting) {
+            let extra = (size - *allocation).min(remaining);
+            *allocation += extra;
+            remaining -= extra;
+        }
+        allocations
+    }
+}
+
+// The first `priority_orders` orders in time priority are filled FIFO, typically
+// the order that set the best price, and the rest of the level shares what is
+// left pro-rata
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hybrid {
+    pub priority_orders: usize,
+    pub pro_rata: ProRata,
+}
+
+impl Default for Hybrid {
+    fn default() -> Self {
+        Self {
+            priority_orders: 1,
+            pro_rata: ProRata::default(),
+        }
+    }
+}
+
+impl MatchingPolicy for Hybrid {
+    fn allocate(&self, quantity: Quantity, resting: &[Quantity]) -> Vec<Quantity> {
+        let split = self.priority_orders.min(resting.len());
+        let mut allocations = Fifo.allocate(quantity, &resting[..split]);
+        let remaining = quantity - allocations.iter().sum::<Quantity>();
+        allocations.extend(self.pro_rata.allocate(remaining, &resting[split..]));
+        allocations
+    }
+}
@@ -0,0 +1,75 @@
+use crate::auction::TradingPhase;
+use crate::execution::ExecutionReport;
+use crate::order_book::{Price, Quantity, Trade};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// Best bid and ask with the quantity shown at each
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopOfBook {
+    pub bid: Option<(Price, Quantity)>,
+    pub ask: Option<(Price, Quantity)>,
+}
+
+// Something that happened in a MarketSimulator. Order events carry the report
+// the order's owner was given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MarketEvent {
+    // Taken by the book, replaced by an amend, or queued during a halt
+    OrderAccepted(ExecutionReport),
+    // Refused by the simulator's checks or by the book
+    OrderRejected(ExecutionReport),
+    // With both legs' fees charged
+    Traded(Trade),
+    // Cancelled on request or by self-trade prevention, left unfilled by a
+    // market, IOC or FOK order, or expired
+    OrderCancelled(ExecutionReport),
+    TopOfBookChanged {
+        top_of_book: TopOfBook,
+        timestamp: DateTime<Utc>,
+    },
+    PhaseChanged {
+        from: TradingPhase,
+        to: TradingPhase,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+// Registered with MarketSimulator::add_observer. Every observer sees every event
+// as it happens, in registration order, so within one request events arrive in
+// the order they happened: an order's acceptance before its trades, a circuit
+// breaker halt straight after the trade that tripped it, each trade before the
+// cancel of an unfilled remainder, and the top of book once the book has settled.
+pub trait MarketObserver: fmt::Debug {
+    fn on_event(&mut self, event: &MarketEvent);
+}
+
+// Keeps every event it sees. Clones share the same log, so a clone kept after
+// handing one to a simulator reads what it recorded.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    events: Arc<Mutex<Vec<MarketEvent>>>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<MarketEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    // Empties the log, returning what it held
+    pub fn take(&self) -> Vec<MarketEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl MarketObserver for EventLog {
+    fn on_event(&mut self, event: &MarketEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
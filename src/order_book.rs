@@ -1,55 +1,241 @@
+use crate::account::Cash;
+use crate::arena::{OrderArena, OrderKey, OrderQueue};
+use crate::auction::{self, IndicativeUncross};
+use crate::clock::{Clock, WallClock};
+use crate::execution::{Execution, ExecutionReport, ExecutionType};
+use crate::halt::{Breach, CircuitBreaker, PriceWindow};
+use crate::ids::{IdGenerator, RandomIds};
+use crate::market_data::{L3Message, L3Publisher, OrderEventKind, RestingOrder};
+use crate::matching::{Fifo, MatchingPolicy};
+use crate::snapshot::BookSnapshot;
 use chrono::{DateTime, Utc};
-use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 use uuid::Uuid;
 
-pub type Price = OrderedFloat<f64>;
+// Fixed-point price in integer units of the instrument's price scale
+pub type Price = i64;
 pub type Quantity = u64;
 pub type OrderId = Uuid;
+pub type AccountId = u64;
+pub type DepthLevels = Vec<(Price, Quantity)>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderSide {
     Buy,
     Sell,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderType {
     Limit,
+    // Executes against the opposite side at any price; never rests in the book
+    Market,
+    // Held in the trigger book until the last trade reaches `stop_price`, then
+    // released as a market order
+    Stop,
+    // As `Stop`, but released as a limit order at `price`
+    StopLimit,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeInForce {
+    GoodTillCancel,
+    // Fill what is possible immediately, cancel the rest
+    ImmediateOrCancel,
+    // Fill the whole quantity immediately or do nothing at all
+    FillOrKill,
+    // Rests until the end of the trading day it was entered on
+    Day,
+    GoodTillDate(DateTime<Utc>),
+}
+
+// What to do when an incoming order would trade against a resting order from the
+// same account or self-trade group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    // Cancel the rest of the incoming order
+    CancelNewest,
+    // Cancel the resting order and keep matching
+    CancelOldest,
+    CancelBoth,
+    // Reduce both orders by the smaller remaining quantity, cancelling whichever
+    // has nothing left
+    DecrementAndCancel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderStatus {
     Open,
     PartiallyFilled,
     Filled,
     Cancelled,
+    Expired,
+    // Refused by the book and never accepted
+    Rejected,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
 pub struct Order {
     pub id: OrderId,
+    // Participant that owns the order; 0 when no account was given
+    pub account_id: AccountId,
     pub side: OrderSide,
     pub order_type: OrderType,
     pub price: Price,
+    pub stop_price: Option<Price>,
     pub quantity: Quantity,
     pub filled_quantity: Quantity,
+    // Sum of price * quantity over every fill, for the average fill price
+    pub filled_notional: i128,
+    // Iceberg orders show at most `peak_quantity` in the book; `displayed_quantity`
+    // is what is left of the slice currently on show
+    pub peak_quantity: Option<Quantity>,
+    pub displayed_quantity: Quantity,
     pub status: OrderStatus,
+    pub time_in_force: TimeInForce,
+    // Set on the incoming order to stop it trading with its own account or group
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    // Ties accounts together for self-trade prevention, e.g. desks of one firm
+    pub self_trade_group: Option<u64>,
     pub timestamp: DateTime<Utc>,
 }
 
 impl Order {
-    pub fn new(side: OrderSide, price: f64, quantity: Quantity) -> Self {
+    // A random ID and the wall clock time. MarketSimulator restamps the time with
+    // its own clock on submission; `with_id` sets the ID.
+    pub fn new(side: OrderSide, price: Price, quantity: Quantity) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id: RandomIds.next_id(),
+            account_id: 0,
             side,
             order_type: OrderType::Limit,
-            price: OrderedFloat(price),
+            price,
+            stop_price: None,
             quantity,
             filled_quantity: 0,
+            filled_notional: 0,
+            peak_quantity: None,
+            displayed_quantity: quantity,
             status: OrderStatus::Open,
-            timestamp: Utc::now(),
+            time_in_force: TimeInForce::GoodTillCancel,
+            self_trade_prevention: None,
+            self_trade_group: None,
+            timestamp: WallClock.now(),
+        }
+    }
+
+    pub fn market(side: OrderSide, quantity: Quantity) -> Self {
+        Self {
+            order_type: OrderType::Market,
+            ..Self::new(side, 0, quantity)
+        }
+    }
+
+    pub fn stop(side: OrderSide, stop_price: Price, quantity: Quantity) -> Self {
+        Self {
+            order_type: OrderType::Stop,
+            stop_price: Some(stop_price),
+            ..Self::market(side, quantity)
+        }
+    }
+
+    pub fn stop_limit(side: OrderSide, stop_price: Price, limit_price: Price, quantity: Quantity) -> Self {
+        Self {
+            order_type: OrderType::StopLimit,
+            stop_price: Some(stop_price),
+            ..Self::new(side, limit_price, quantity)
+        }
+    }
+
+    pub fn iceberg(side: OrderSide, price: Price, quantity: Quantity, peak_quantity: Quantity) -> Self {
+        Self {
+            peak_quantity: Some(peak_quantity),
+            displayed_quantity: peak_quantity.min(quantity),
+            ..Self::new(side, price, quantity)
+        }
+    }
+
+    pub fn with_id(mut self, id: OrderId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_account(mut self, account_id: AccountId) -> Self {
+        self.account_id = account_id;
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_self_trade_prevention(mut self, mode: SelfTradePrevention) -> Self {
+        self.self_trade_prevention = Some(mode);
+        self
+    }
+
+    pub fn with_self_trade_group(mut self, group: u64) -> Self {
+        self.self_trade_group = Some(group);
+        self
+    }
+
+    // Whether matching this incoming order against `resting` is a self-trade it
+    // has asked to prevent
+    pub fn prevents_trade_with(&self, resting: &Order) -> bool {
+        self.self_trade_prevention.is_some()
+            && (self.account_id == resting.account_id
+                || (self.self_trade_group.is_some() && self.self_trade_group == resting.self_trade_group))
+    }
+
+    // Whether any unfilled quantity may rest in the book after matching
+    pub fn can_rest(&self) -> bool {
+        self.order_type == OrderType::Limit
+            && !matches!(
+                self.time_in_force,
+                TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+            )
+    }
+
+    // Still working: resting in the book or waiting in the trigger book
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, OrderStatus::Open | OrderStatus::PartiallyFilled)
+    }
+
+    pub fn is_stop(&self) -> bool {
+        matches!(self.order_type, OrderType::Stop | OrderType::StopLimit)
+    }
+
+    // Turns a triggered stop into the order it stands for
+    fn trigger(&mut self) {
+        self.order_type = match self.order_type {
+            OrderType::Stop => OrderType::Market,
+            OrderType::StopLimit => OrderType::Limit,
+            other => other,
+        };
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        match self.time_in_force {
+            TimeInForce::Day => now.date_naive() > self.timestamp.date_naive(),
+            TimeInForce::GoodTillDate(expiry) => now >= expiry,
+            _ => false,
+        }
+    }
+
+    // Whether this order is willing to trade against a resting order at `price`
+    pub fn crosses(&self, price: Price) -> bool {
+        match (self.order_type, self.side) {
+            (OrderType::Market, _) => true,
+            (OrderType::Limit, OrderSide::Buy) => self.price >= price,
+            (OrderType::Limit, OrderSide::Sell) => self.price <= price,
+            // Stops do not trade until they are triggered
+            (OrderType::Stop | OrderType::StopLimit, _) => false,
         }
     }
 
@@ -57,12 +243,44 @@ impl Order {
         self.quantity - self.filled_quantity
     }
 
+    // Quantity shown in the book: the current slice for icebergs, everything otherwise
+    pub fn visible_quantity(&self) -> Quantity {
+        match self.peak_quantity {
+            Some(_) => self.displayed_quantity,
+            None => self.remaining_quantity(),
+        }
+    }
+
+    pub fn reserve_quantity(&self) -> Quantity {
+        self.remaining_quantity() - self.visible_quantity()
+    }
+
+    // Shows a fresh iceberg slice from the reserve
+    fn refresh_display(&mut self) {
+        if let Some(peak_quantity) = self.peak_quantity {
+            self.displayed_quantity = peak_quantity.max(1).min(self.remaining_quantity());
+        }
+    }
+
     pub fn is_complete(&self) -> bool {
         self.filled_quantity >= self.quantity
     }
 
-    pub fn fill(&mut self, quantity: Quantity) {
+    // Volume-weighted price of the fills so far, in price units
+    pub fn average_price(&self) -> Option<f64> {
+        (self.filled_quantity > 0).then(|| self.filled_notional as f64 / self.filled_quantity as f64)
+    }
+
+    // Takes `quantity` off the order without trading it
+    fn decrement(&mut self, quantity: Quantity) {
+        self.quantity -= quantity;
+        self.displayed_quantity = self.displayed_quantity.min(self.remaining_quantity());
+    }
+
+    pub fn fill(&mut self, quantity: Quantity, price: Price) {
         self.filled_quantity += quantity;
+        self.filled_notional += price as i128 * quantity as i128;
+        self.displayed_quantity = self.displayed_quantity.saturating_sub(quantity);
         if self.is_complete() {
             self.status = OrderStatus::Filled;
         } else {
@@ -71,246 +289,736 @@ impl Order {
     }
 }
 
+// Which side of a trade a leg was on: the resting order provided liquidity,
+// the incoming order took it. Auction trades have neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+    Auction,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub id: Uuid,
     pub buy_order_id: OrderId,
     pub sell_order_id: OrderId,
+    pub buy_account_id: AccountId,
+    pub sell_account_id: AccountId,
     pub price: Price,
     pub quantity: Quantity,
+    // Side of the incoming order that caused the trade; `None` for auction trades
+    pub aggressor_side: Option<OrderSide>,
+    pub buy_liquidity: Liquidity,
+    pub sell_liquidity: Liquidity,
+    // Charged to each leg by the simulator's fee schedule; negative for rebates
+    pub buy_fee: Cash,
+    pub sell_fee: Cash,
     pub timestamp: DateTime<Utc>,
 }
 
 impl Trade {
-    pub fn new(buy_order_id: OrderId, sell_order_id: OrderId, price: Price, quantity: Quantity) -> Self {
+    pub fn new(
+        buy_order: &Order,
+        sell_order: &Order,
+        aggressor_side: Option<OrderSide>,
+        price: Price,
+        quantity: Quantity,
+        id: Uuid,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        let (buy_liquidity, sell_liquidity) = match aggressor_side {
+            Some(OrderSide::Buy) => (Liquidity::Taker, Liquidity::Maker),
+            Some(OrderSide::Sell) => (Liquidity::Maker, Liquidity::Taker),
+            None => (Liquidity::Auction, Liquidity::Auction),
+        };
         Self {
-            id: Uuid::new_v4(),
-            buy_order_id,
-            sell_order_id,
+            id,
+            buy_order_id: buy_order.id,
+            sell_order_id: sell_order.id,
+            buy_account_id: buy_order.account_id,
+            sell_account_id: sell_order.account_id,
             price,
             quantity,
-            timestamp: Utc::now(),
+            aggressor_side,
+            buy_liquidity,
+            sell_liquidity,
+            buy_fee: 0,
+            sell_fee: 0,
+            timestamp,
+        }
+    }
+
+    pub fn liquidity(&self, side: OrderSide) -> Liquidity {
+        match side {
+            OrderSide::Buy => self.buy_liquidity,
+            OrderSide::Sell => self.sell_liquidity,
+        }
+    }
+
+    pub fn fee(&self, side: OrderSide) -> Cash {
+        match side {
+            OrderSide::Buy => self.buy_fee,
+            OrderSide::Sell => self.sell_fee,
+        }
+    }
+
+    // Quantity signed by the aggressor: positive when a buyer took liquidity,
+    // zero for auction trades
+    pub fn signed_quantity(&self) -> i64 {
+        match self.aggressor_side {
+            Some(OrderSide::Buy) => self.quantity as i64,
+            Some(OrderSide::Sell) => -(self.quantity as i64),
+            None => 0,
         }
     }
 }
 
+// One side of the book: for each price, the queue of orders resting there in
+// time priority
+#[derive(Debug, Clone)]
+struct Ladder {
+    side: OrderSide,
+    levels: BTreeMap<Price, OrderQueue>,
+}
+
+impl Ladder {
+    fn new(side: OrderSide) -> Self {
+        Self {
+            side,
+            levels: BTreeMap::new(),
+        }
+    }
+
+    // Highest bid or lowest ask
+    fn best_price(&self) -> Option<Price> {
+        match self.side {
+            OrderSide::Buy => self.levels.keys().next_back().copied(),
+            OrderSide::Sell => self.levels.keys().next().copied(),
+        }
+    }
+
+    // The next level away from the best price after `price`; the best level if
+    // there is no `price` yet
+    fn next_price(&self, price: Option<Price>) -> Option<Price> {
+        let Some(price) = price else {
+            return self.best_price();
+        };
+        let next = match self.side {
+            OrderSide::Buy => self.levels.range(..price).next_back(),
+            OrderSide::Sell => self.levels.range((Bound::Excluded(price), Bound::Unbounded)).next(),
+        };
+        next.map(|(&price, _)| price)
+    }
+
+    // Levels from the best price outwards
+    fn levels(&self) -> Box<dyn Iterator<Item = (&Price, &OrderQueue)> + '_> {
+        match self.side {
+            OrderSide::Buy => Box::new(self.levels.iter().rev()),
+            OrderSide::Sell => Box::new(self.levels.iter()),
+        }
+    }
+
+    // First order in time priority at the best price
+    fn front(&self) -> Option<OrderKey> {
+        self.levels().next().and_then(|(_, level)| level.front())
+    }
+
+    fn push(&mut self, orders: &mut OrderArena, price: Price, key: OrderKey) {
+        self.levels.entry(price).or_default().push_back(orders, key);
+    }
+
+    fn remove(&mut self, orders: &mut OrderArena, price: Price, key: OrderKey) {
+        remove_key(&mut self.levels, orders, price, key);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OrderBook {
     // Buy orders sorted by price (highest first), then by time (earliest first)
-    buy_orders: BTreeMap<Price, Vec<Order>>,
+    buy_orders: Ladder,
     // Sell orders sorted by price (lowest first), then by time (earliest first)
-    sell_orders: BTreeMap<Price, Vec<Order>>,
-    // All orders by ID for quick lookup
-    orders: HashMap<OrderId, Order>,
+    sell_orders: Ladder,
+    // Untriggered buy stops by stop price, released lowest first once the last
+    // trade is at or above the stop price
+    buy_stops: BTreeMap<Price, OrderQueue>,
+    // Untriggered sell stops by stop price, released highest first once the last
+    // trade is at or below the stop price
+    sell_stops: BTreeMap<Price, OrderQueue>,
+    // Owns every order, resting or done; the ladders and trigger books above
+    // refer to orders by their key here
+    orders: OrderArena,
+    last_trade_price: Option<Price>,
+    // While collecting a call auction, orders rest without matching
+    in_auction: bool,
+    // How each price level shares an incoming order among its resting orders
+    matching_policy: Arc<dyn MatchingPolicy>,
+    // Gives trades their IDs. The book never reads a clock: a request happens at
+    // its order's timestamp or at the `now` it is given.
+    ids: Arc<dyn IdGenerator>,
+    // When set, orders that are done trading leave `orders` for `closed_orders`
+    // instead of staying on record
+    evict_closed_orders: bool,
+    closed_orders: Vec<Order>,
+    // Every change to a resting order, as it happens
+    order_feed: L3Publisher,
+    // Matching stops at a trade that moves too far from the recent trades in
+    // `price_window`; off unless set. Auctions feed the window but never trip it.
+    circuit_breaker: Option<CircuitBreaker>,
+    price_window: PriceWindow,
 }
 
 impl OrderBook {
     pub fn new() -> Self {
         Self {
-            buy_orders: BTreeMap::new(),
-            sell_orders: BTreeMap::new(),
-            orders: HashMap::new(),
+            buy_orders: Ladder::new(OrderSide::Buy),
+            sell_orders: Ladder::new(OrderSide::Sell),
+            buy_stops: BTreeMap::new(),
+            sell_stops: BTreeMap::new(),
+            orders: OrderArena::new(),
+            last_trade_price: None,
+            in_auction: false,
+            matching_policy: Arc::new(Fifo),
+            ids: Arc::new(RandomIds),
+            evict_closed_orders: false,
+            closed_orders: Vec::new(),
+            order_feed: L3Publisher::new(),
+            circuit_breaker: None,
+            price_window: PriceWindow::default(),
         }
     }
 
-    pub fn add_order(&mut self, order: Order) -> Vec<Trade> {
-        let mut trades = Vec::new();
-        
-        match order.side {
-            OrderSide::Buy => {
-                trades.extend(self.match_buy_order(order));
-            }
-            OrderSide::Sell => {
-                trades.extend(self.match_sell_order(order));
-            }
-        }
+    pub fn set_circuit_breaker(&mut self, breaker: Option<CircuitBreaker>) {
+        self.circuit_breaker = breaker;
+    }
 
-        trades
+    // Recent trade prices the circuit breaker compares new trades with
+    pub fn price_window(&self) -> &PriceWindow {
+        &self.price_window
     }
 
-    fn match_buy_order(&mut self, mut buy_order: Order) -> Vec<Trade> {
-        let mut trades = Vec::new();
+    pub fn set_price_window(&mut self, price_window: PriceWindow) {
+        self.price_window = price_window;
+    }
 
-        // Try to match against existing sell orders
-        let mut prices_to_remove = Vec::new();
-        
-        for (&sell_price, sell_orders) in self.sell_orders.iter_mut() {
-            if buy_order.price < sell_price {
-                break; // No more matches possible
-            }
+    pub fn matching_policy(&self) -> &dyn MatchingPolicy {
+        &*self.matching_policy
+    }
 
-            let mut orders_to_remove = Vec::new();
-            
-            for (index, sell_order) in sell_orders.iter_mut().enumerate() {
-                if buy_order.remaining_quantity() == 0 {
-                    break;
-                }
+    // Replaces the within-level allocation; price priority across levels is kept
+    pub fn set_matching_policy<P: MatchingPolicy + 'static>(&mut self, policy: P) {
+        self.matching_policy = Arc::new(policy);
+    }
 
-                let trade_quantity = buy_order.remaining_quantity().min(sell_order.remaining_quantity());
-                let trade_price = sell_price; // Use the sell order's price
+    pub fn id_generator(&self) -> &Arc<dyn IdGenerator> {
+        &self.ids
+    }
 
-                // Create trade
-                let trade = Trade::new(buy_order.id, sell_order.id, trade_price, trade_quantity);
-                trades.push(trade);
+    // Shared rather than owned, so a MarketSimulator can number its orders from
+    // the same sequence as the book's trades
+    pub fn set_id_generator(&mut self, ids: Arc<dyn IdGenerator>) {
+        self.ids = ids;
+    }
 
-                // Update orders
-                buy_order.fill(trade_quantity);
-                sell_order.fill(trade_quantity);
+    // Off by default: filled orders, and incoming orders cancelled or expired
+    // without resting, stay available from `get_order`. When on, they and any
+    // resting orders expired or cancelled while matching are collected for
+    // `drain_closed_orders` instead, so the book only holds working orders.
+    pub fn set_evict_closed_orders(&mut self, evict: bool) {
+        self.evict_closed_orders = evict;
+    }
 
-                // Update orders in the main HashMap
-                self.orders.insert(buy_order.id, buy_order.clone());
-                self.orders.insert(sell_order.id, sell_order.clone());
+    // Orders evicted since the last call, in the order they closed
+    pub fn drain_closed_orders(&mut self) -> Vec<Order> {
+        std::mem::take(&mut self.closed_orders)
+    }
 
-                if sell_order.is_complete() {
-                    orders_to_remove.push(index);
-                }
-            }
+    pub fn order_feed(&self) -> &L3Publisher {
+        &self.order_feed
+    }
 
-            // Remove completed orders
-            for &index in orders_to_remove.iter().rev() {
-                sell_orders.remove(index);
-            }
+    // A level 3 stream of this book: a snapshot of every resting order, then an
+    // event for each order added, executed, reduced or deleted
+    pub fn subscribe_order_events(&mut self) -> Receiver<L3Message> {
+        let (bids, asks) = (self.resting_orders(OrderSide::Buy), self.resting_orders(OrderSide::Sell));
+        self.order_feed.subscribe(bids, asks)
+    }
 
-            if sell_orders.is_empty() {
-                prices_to_remove.push(sell_price);
-            }
+    // Number of orders on record, working or not
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
 
-            if buy_order.is_complete() {
-                break;
-            }
+    // Records an order that will not trade again
+    pub fn close_order(&mut self, order: Order) {
+        if self.evict_closed_orders {
+            self.closed_orders.push(order);
+        } else {
+            self.orders.insert(order);
         }
+    }
 
-        // Remove empty price levels
-        for price in prices_to_remove {
-            self.sell_orders.remove(&price);
+    // Matches or rests `order` and releases any stops its trades trigger. The
+    // execution holds the resulting trades and a report for every order touched,
+    // starting with the new order's acknowledgement (or rejection).
+    // An order reusing the ID of one still on record is rejected
+    pub fn add_order(&mut self, order: Order) -> Execution {
+        if self.orders.get(&order.id).is_some() {
+            let mut execution = Execution::new(order.timestamp);
+            execution.reports.push(ExecutionReport::rejected(&order, "order ID is already in use", order.timestamp));
+            return execution;
         }
+        self.accept_order(order, ExecutionType::New)
+    }
 
-        // If buy order still has remaining quantity, add it to the book
-        if !buy_order.is_complete() {
-            self.orders.insert(buy_order.id, buy_order.clone());
-            self.buy_orders
-                .entry(buy_order.price)
-                .or_insert_with(Vec::new)
-                .push(buy_order);
+    fn accept_order(&mut self, order: Order, acknowledgement: ExecutionType) -> Execution {
+        let now = order.timestamp;
+        self.accept_order_at(order, acknowledgement, now)
+    }
+
+    fn accept_order_at(&mut self, order: Order, acknowledgement: ExecutionType, now: DateTime<Utc>) -> Execution {
+        let mut execution = Execution::new(now);
+
+        if let Some(reason) = rejection_reason(&order) {
+            execution.reports.push(ExecutionReport::rejected(&order, reason, now));
+            return execution;
+        }
+
+        if self.in_auction && !order.is_stop() && !order.can_rest() {
+            let reason = "only limit orders that can rest may join an auction";
+            execution.reports.push(ExecutionReport::rejected(&order, reason, now));
+            return execution;
+        }
+
+        if order.is_stop() {
+            execution.reports.push(ExecutionReport::new(&order, acknowledgement, now));
+            self.add_stop_order(order);
+        } else if self.in_auction {
+            execution.reports.push(ExecutionReport::new(&order, acknowledgement, now));
+            self.rest_order(order, now);
         } else {
-            self.orders.insert(buy_order.id, buy_order);
+            self.execute_order(order, acknowledgement, &mut execution);
         }
 
-        trades
+        if self.in_auction {
+            return execution;
+        }
+        if let Some(trade) = execution.trades.last() {
+            self.last_trade_price = Some(trade.price);
+        }
+        self.release_triggered_stops(&mut execution);
+
+        execution
     }
 
-    fn match_sell_order(&mut self, mut sell_order: Order) -> Vec<Trade> {
-        let mut trades = Vec::new();
+    fn rest_order(&mut self, mut order: Order, now: DateTime<Utc>) {
+        order.refresh_display();
+        self.order_feed.publish(OrderEventKind::Add, &order, order.visible_quantity(), now);
+        let (side, price) = (order.side, order.price);
+        let key = self.orders.insert(order);
+        let ladder = match side {
+            OrderSide::Buy => &mut self.buy_orders,
+            OrderSide::Sell => &mut self.sell_orders,
+        };
+        ladder.push(&mut self.orders, price, key);
+    }
 
-        // Try to match against existing buy orders (highest price first)
-        let mut prices_to_remove = Vec::new();
-        
-        for (&buy_price, buy_orders) in self.buy_orders.iter_mut().rev() {
-            if sell_order.price > buy_price {
-                break; // No more matches possible
-            }
+    // Starts collecting orders for a call auction. Until `uncross`, new and amended
+    // orders rest without matching, so the book may be crossed.
+    pub fn begin_auction(&mut self) {
+        self.in_auction = true;
+    }
 
-            let mut orders_to_remove = Vec::new();
-            
-            for (index, buy_order) in buy_orders.iter_mut().enumerate() {
-                if sell_order.remaining_quantity() == 0 {
-                    break;
-                }
+    pub fn is_in_auction(&self) -> bool {
+        self.in_auction
+    }
 
-                let trade_quantity = sell_order.remaining_quantity().min(buy_order.remaining_quantity());
-                let trade_price = buy_price; // Use the buy order's price
+    // Price and volume the auction would uncross at now; `None` if nothing would
+    // trade. `reference_price` settles ties, typically the last trade price.
+    pub fn indicative_uncross(&self, reference_price: Option<Price>) -> Option<IndicativeUncross> {
+        auction::find_uncross(
+            &self.level_quantities(&self.buy_orders),
+            &self.level_quantities(&self.sell_orders),
+            reference_price,
+        )
+    }
+
+    // Unfilled quantity at each price of a ladder, hidden iceberg reserve included
+    fn level_quantities(&self, ladder: &Ladder) -> BTreeMap<Price, Quantity> {
+        ladder
+            .levels
+            .iter()
+            .map(|(&price, level)| {
+                let quantity = level.iter(&self.orders).map(|key| self.orders[key].remaining_quantity()).sum();
+                (price, quantity)
+            })
+            .collect()
+    }
 
-                // Create trade
-                let trade = Trade::new(buy_order.id, sell_order.id, trade_price, trade_quantity);
-                trades.push(trade);
+    // Ends the auction by trading everything executable at the single uncrossing
+    // price, in price-time priority on both sides. Hidden iceberg reserve trades
+    // too, and an iceberg left showing nothing refreshes at the back of its level.
+    // Self-trade prevention does not apply here; triggered stops wait for
+    // `release_stops`.
+    pub fn uncross(&mut self, reference_price: Option<Price>, now: DateTime<Utc>) -> Execution {
+        self.in_auction = false;
+        let mut execution = Execution::new(now);
+        let Some(uncross) = self.indicative_uncross(reference_price) else {
+            return execution;
+        };
 
-                // Update orders
-                sell_order.fill(trade_quantity);
-                buy_order.fill(trade_quantity);
+        let mut remaining = uncross.volume;
+        while remaining > 0 {
+            let (Some(bid), Some(ask)) = (self.buy_orders.front(), self.sell_orders.front()) else {
+                break;
+            };
+            let (buy_order, sell_order) = self.orders.pair_mut(bid, ask);
 
-                // Update orders in the main HashMap
-                self.orders.insert(sell_order.id, sell_order.clone());
-                self.orders.insert(buy_order.id, buy_order.clone());
+            let quantity = remaining
+                .min(buy_order.remaining_quantity())
+                .min(sell_order.remaining_quantity());
+            let trade = Trade::new(buy_order, sell_order, None, uncross.price, quantity, self.ids.next_id(), now);
+            buy_order.fill(quantity, uncross.price);
+            sell_order.fill(quantity, uncross.price);
+            execution.reports.push(ExecutionReport::fill(buy_order, &trade));
+            execution.reports.push(ExecutionReport::fill(sell_order, &trade));
+            self.order_feed.publish(OrderEventKind::Execute, buy_order, quantity, now);
+            self.order_feed.publish(OrderEventKind::Execute, sell_order, quantity, now);
+            if let Some(breaker) = &self.circuit_breaker {
+                self.price_window.record(breaker, now, uncross.price);
+            }
+            execution.trades.push(trade);
+            remaining -= quantity;
 
-                if buy_order.is_complete() {
-                    orders_to_remove.push(index);
+            for (ladder, key) in [(&mut self.buy_orders, bid), (&mut self.sell_orders, ask)] {
+                let order = &mut self.orders[key];
+                let price = order.price;
+                if order.is_complete() {
+                    ladder.remove(&mut self.orders, price, key);
+                    if self.evict_closed_orders {
+                        self.closed_orders.push(self.orders.remove(key));
+                    }
+                } else if order.visible_quantity() == 0 {
+                    order.refresh_display();
+                    self.order_feed.publish(OrderEventKind::Add, order, order.visible_quantity(), now);
+                    ladder.remove(&mut self.orders, price, key);
+                    ladder.push(&mut self.orders, price, key);
                 }
             }
+        }
 
-            // Remove completed orders
-            for &index in orders_to_remove.iter().rev() {
-                buy_orders.remove(index);
-            }
+        if !execution.trades.is_empty() {
+            self.last_trade_price = Some(uncross.price);
+        }
+        execution
+    }
 
-            if buy_orders.is_empty() {
-                prices_to_remove.push(buy_price);
-            }
+    // Releases every stop triggered by the last trade price, e.g. once continuous
+    // trading follows an auction
+    pub fn release_stops(&mut self, now: DateTime<Utc>) -> Execution {
+        let mut execution = Execution::new(now);
+        self.release_triggered_stops(&mut execution);
+        execution
+    }
+
+    fn add_stop_order(&mut self, order: Order) {
+        let Some(stop_price) = order.stop_price else {
+            return;
+        };
+        let side = order.side;
+        let key = self.orders.insert(order);
+        let stops = match side {
+            OrderSide::Buy => &mut self.buy_stops,
+            OrderSide::Sell => &mut self.sell_stops,
+        };
+        stops.entry(stop_price).or_default().push_back(&mut self.orders, key);
+    }
 
-            if sell_order.is_complete() {
+    // Releases triggered stops one at a time. Each released order may trade and move
+    // the last trade price, which can trigger further stops, so the trigger book is
+    // re-examined after every release until nothing more fires.
+    // Released stops were acknowledged on entry, so they report fills only, but
+    // they still expire or are killed like any other order reaching the book.
+    fn release_triggered_stops(&mut self, execution: &mut Execution) {
+        while execution.breach.is_none() {
+            let Some(mut order) = self.next_triggered_stop() else {
                 break;
+            };
+            order.trigger();
+            if let Some(order) = self.screen_order(order, execution) {
+                self.match_order(order, execution);
+            }
+            if let Some(trade) = execution.trades.last() {
+                self.last_trade_price = Some(trade.price);
+            }
+        }
+    }
+
+    // Picks the triggered stop with the best stop price on its side (lowest buy stop,
+    // highest sell stop), earliest first within a price. If both sides have a triggered
+    // stop the one entered earlier goes first, buys winning exact ties.
+    fn next_triggered_stop(&mut self) -> Option<Order> {
+        let last_trade_price = self.last_trade_price?;
+
+        let buy = self
+            .buy_stops
+            .range(..=last_trade_price)
+            .next()
+            .and_then(|(&price, level)| Some((price, self.orders[level.front()?].timestamp)));
+        let sell = self
+            .sell_stops
+            .range(last_trade_price..)
+            .next_back()
+            .and_then(|(&price, level)| Some((price, self.orders[level.front()?].timestamp)));
+
+        let (stops, price) = match (buy, sell) {
+            (Some((_, buy_time)), Some((sell_price, sell_time))) if sell_time < buy_time => {
+                (&mut self.sell_stops, sell_price)
             }
+            (Some((buy_price, _)), _) => (&mut self.buy_stops, buy_price),
+            (None, Some((sell_price, _))) => (&mut self.sell_stops, sell_price),
+            (None, None) => return None,
+        };
+
+        let level = stops.get_mut(&price)?;
+        let key = level.pop_front(&mut self.orders)?;
+        if level.is_empty() {
+            stops.remove(&price);
         }
+        Some(self.orders.remove(key))
+    }
+
+    fn execute_order(&mut self, order: Order, acknowledgement: ExecutionType, execution: &mut Execution) {
+        let Some(order) = self.screen_order(order, execution) else {
+            return;
+        };
+        execution.reports.push(ExecutionReport::new(&order, acknowledgement, execution.timestamp));
+        self.match_order(order, execution);
+    }
 
-        // Remove empty price levels
-        for price in prices_to_remove {
-            self.buy_orders.remove(&price);
+    // Expires an order whose time is up and kills a fill-or-kill order the book
+    // cannot fill, reporting either; returns the order if it may go on to match
+    fn screen_order(&mut self, mut order: Order, execution: &mut Execution) -> Option<Order> {
+        if order.is_expired(execution.timestamp) {
+            order.status = OrderStatus::Expired;
+            execution.reports.push(ExecutionReport::new(&order, ExecutionType::Expired, execution.timestamp));
+            self.close_order(order);
+            return None;
         }
 
-        // If sell order still has remaining quantity, add it to the book
-        if !sell_order.is_complete() {
-            self.orders.insert(sell_order.id, sell_order.clone());
-            self.sell_orders
-                .entry(sell_order.price)
-                .or_insert_with(Vec::new)
-                .push(sell_order);
-        } else {
-            self.orders.insert(sell_order.id, sell_order);
+        // Fill-or-kill must be checked against the book before anything is matched
+        if order.time_in_force == TimeInForce::FillOrKill
+            && self.available_quantity(&order) < order.quantity
+        {
+            order.status = OrderStatus::Cancelled;
+            execution.reports.push(ExecutionReport::new(&order, ExecutionType::Cancelled, execution.timestamp));
+            self.close_order(order);
+            return None;
         }
 
-        trades
+        Some(order)
     }
 
-    pub fn cancel_order(&mut self, order_id: OrderId) -> Option<Order> {
-        if let Some(mut order) = self.orders.remove(&order_id) {
+    // Matches `order` against the opposite side from the best price outwards, then
+    // rests, cancels or records whatever is left of it. If the circuit breaker
+    // trips, what is left of an order that could rest goes back in
+    // `execution.interrupted` instead.
+    fn match_order(&mut self, mut order: Order, execution: &mut Execution) {
+        let opposite = match order.side {
+            OrderSide::Buy => &mut self.sell_orders,
+            OrderSide::Sell => &mut self.buy_orders,
+        };
+
+        let mut last_price = None;
+        while order.is_active() && execution.breach.is_none() {
+            let Some(price) = opposite.next_price(last_price).filter(|&price| order.crosses(price)) else {
+                break; // No more matches possible
+            };
+            last_price = Some(price);
+
+            let level = opposite.levels.get_mut(&price).expect("ladder levels are never empty");
+            let closed = self.evict_closed_orders.then_some(&mut self.closed_orders);
+            let mut matcher = Matcher {
+                policy: &*self.matching_policy,
+                ids: &*self.ids,
+                feed: &mut self.order_feed,
+                circuit_breaker: self.circuit_breaker.as_ref(),
+                price_window: &mut self.price_window,
+            };
+            // Trades at the resting order's price
+            match_level(&mut order, level, price, &mut matcher, &mut self.orders, closed, execution);
+            if level.is_empty() {
+                opposite.levels.remove(&price);
+            }
+        }
+
+        // If the order still has remaining quantity, add it to the book.
+        // Market and IOC orders never rest: whatever is left once the book runs out is cancelled.
+        if order.status == OrderStatus::Cancelled {
+            // Cancelled by self-trade prevention, which has already reported it
+            self.close_order(order);
+        } else if !order.is_complete() && !order.can_rest() {
             order.status = OrderStatus::Cancelled;
-            
-            // Remove from the appropriate side of the book
-            match order.side {
-                OrderSide::Buy => {
-                    if let Some(orders) = self.buy_orders.get_mut(&order.price) {
-                        orders.retain(|o| o.id != order_id);
-                        if orders.is_empty() {
-                            self.buy_orders.remove(&order.price);
-                        }
-                    }
-                }
-                OrderSide::Sell => {
-                    if let Some(orders) = self.sell_orders.get_mut(&order.price) {
-                        orders.retain(|o| o.id != order_id);
-                        if orders.is_empty() {
-                            self.sell_orders.remove(&order.price);
-                        }
-                    }
+            execution.reports.push(ExecutionReport::new(&order, ExecutionType::Cancelled, execution.timestamp));
+            self.close_order(order);
+        } else if execution.breach.is_some() && !order.is_complete() {
+            execution.interrupted = Some(order);
+        } else if !order.is_complete() {
+            self.rest_order(order, execution.timestamp);
+        } else {
+            self.close_order(order);
+        }
+    }
+
+    // Sum of resting quantity on the opposite side that `order` could trade against.
+    // Levels from the first one whose trade would trip the circuit breaker on are
+    // left out, as the halt would stop matching there.
+    fn available_quantity(&self, order: &Order) -> Quantity {
+        let levels = match order.side {
+            OrderSide::Buy => self.sell_orders.levels(),
+            OrderSide::Sell => self.buy_orders.levels(),
+        };
+
+        let mut window = self.circuit_breaker.map(|breaker| (breaker, self.price_window.clone()));
+        let mut available = 0;
+        for (&price, resting) in levels {
+            if !order.crosses(price) || available >= order.quantity {
+                break;
+            }
+            if let Some((breaker, window)) = &mut window {
+                if window.record(breaker, order.timestamp, price).is_some() {
+                    break;
                 }
             }
-            
-            Some(order)
-        } else {
-            None
+            available += resting
+                .iter(&self.orders)
+                .map(|key| &self.orders[key])
+                .filter(|o| !o.is_expired(order.timestamp) && !order.prevents_trade_with(o))
+                .map(|o| o.remaining_quantity())
+                .sum::<Quantity>();
         }
+        available
+    }
+
+    // Every order still working: resting in either side of the book or waiting
+    // in the trigger book
+    pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+        self.buy_orders
+            .levels
+            .values()
+            .chain(self.sell_orders.levels.values())
+            .chain(self.buy_stops.values())
+            .chain(self.sell_stops.values())
+            .flat_map(|level| level.iter(&self.orders))
+            .map(|key| &self.orders[key])
+    }
+
+    // Removes every resting order whose time in force has lapsed at `now`
+    pub fn expire_orders(&mut self, now: DateTime<Utc>) -> Vec<Order> {
+        let expired_ids: Vec<OrderId> = self
+            .open_orders()
+            .filter(|o| o.is_expired(now))
+            .map(|o| o.id)
+            .collect();
+
+        expired_ids
+            .into_iter()
+            .filter_map(|id| self.remove_order(id, OrderStatus::Expired, now))
+            .collect()
+    }
+
+    // Removes every resting DAY order at the close of the trading session
+    pub fn expire_day_orders(&mut self, now: DateTime<Utc>) -> Vec<Order> {
+        let day_ids: Vec<OrderId> = self
+            .open_orders()
+            .filter(|o| o.time_in_force == TimeInForce::Day)
+            .map(|o| o.id)
+            .collect();
+
+        day_ids
+            .into_iter()
+            .filter_map(|id| self.remove_order(id, OrderStatus::Expired, now))
+            .collect()
+    }
+
+    // Only working orders can be cancelled; filled or already closed orders are left alone
+    pub fn cancel_order(&mut self, order_id: OrderId, now: DateTime<Utc>) -> Option<Order> {
+        if !self.orders.get(&order_id)?.is_active() {
+            return None;
+        }
+        self.remove_order(order_id, OrderStatus::Cancelled, now)
+    }
+
+    // Changes the price and/or total quantity of a resting limit order, keeping its ID.
+    // Reducing the quantity at the same price keeps the order's place in the queue;
+    // any other change sends it to the back of the queue at its (new) price, matching
+    // it first if it now crosses the book. Returns `None` if the order is not resting.
+    pub fn amend_order(
+        &mut self,
+        order_id: OrderId,
+        new_price: Price,
+        new_quantity: Quantity,
+        now: DateTime<Utc>,
+    ) -> Option<Execution> {
+        let key = self.orders.key(&order_id)?;
+        let order = &mut self.orders[key];
+        if order.order_type != OrderType::Limit || !order.is_active() {
+            return None;
+        }
+
+        if new_price == order.price && new_quantity <= order.quantity {
+            let shown = order.visible_quantity();
+            order.quantity = new_quantity;
+            order.displayed_quantity = order.displayed_quantity.min(order.remaining_quantity());
+            if order.visible_quantity() < shown {
+                self.order_feed.publish(OrderEventKind::Reduce, order, shown - order.visible_quantity(), now);
+            }
+            let mut execution = Execution::new(now);
+            execution.reports.push(ExecutionReport::new(order, ExecutionType::Replaced, now));
+            return Some(execution);
+        }
+
+        let status = order.status;
+        let mut order = self.remove_order(order_id, status, now)?;
+        order.price = new_price;
+        order.quantity = new_quantity;
+        order.refresh_display();
+        Some(self.accept_order_at(order, ExecutionType::Replaced, now))
+    }
+
+    fn remove_order(&mut self, order_id: OrderId, status: OrderStatus, now: DateTime<Utc>) -> Option<Order> {
+        let key = self.orders.key(&order_id)?;
+        let order = &self.orders[key];
+        if !order.is_stop() {
+            self.order_feed.publish(OrderEventKind::Delete, order, order.visible_quantity(), now);
+        }
+
+        // Remove from the appropriate side of the book, or from the trigger
+        // book if it is a stop that has not fired yet
+        let (price, stop_price) = (order.price, order.stop_price.unwrap_or(order.price));
+        match (order.side, order.is_stop()) {
+            (OrderSide::Buy, false) => self.buy_orders.remove(&mut self.orders, price, key),
+            (OrderSide::Sell, false) => self.sell_orders.remove(&mut self.orders, price, key),
+            (OrderSide::Buy, true) => remove_key(&mut self.buy_stops, &mut self.orders, stop_price, key),
+            (OrderSide::Sell, true) => remove_key(&mut self.sell_stops, &mut self.orders, stop_price, key),
+        }
+
+        let mut order = self.orders.remove(key);
+        order.status = status;
+        Some(order)
     }
 
     pub fn get_best_bid(&self) -> Option<Price> {
-        self.buy_orders.keys().next_back().copied()
+        self.buy_orders.best_price()
     }
 
     pub fn get_best_ask(&self) -> Option<Price> {
-        self.sell_orders.keys().next().copied()
+        self.sell_orders.best_price()
     }
 
-    pub fn get_spread(&self) -> Option<f64> {
+    pub fn get_spread(&self) -> Option<Price> {
         match (self.get_best_bid(), self.get_best_ask()) {
-            (Some(bid), Some(ask)) => Some(ask.into_inner() - bid.into_inner()),
+            (Some(bid), Some(ask)) => Some(ask - bid),
             _ => None,
         }
     }
@@ -318,39 +1026,127 @@ impl OrderBook {
     pub fn get_spread_percentage(&self) -> Option<f64> {
         match (self.get_best_bid(), self.get_best_ask()) {
             (Some(bid), Some(ask)) => {
-                let mid_price = (bid.into_inner() + ask.into_inner()) / 2.0;
-                let spread = ask.into_inner() - bid.into_inner();
+                let mid_price = (bid + ask) as f64 / 2.0;
+                let spread = (ask - bid) as f64;
                 Some(spread / mid_price * 100.0)
             }
             _ => None,
         }
     }
 
+    pub fn get_last_trade_price(&self) -> Option<Price> {
+        self.last_trade_price
+    }
+
     pub fn get_order(&self, order_id: &OrderId) -> Option<&Order> {
         self.orders.get(order_id)
     }
 
-    pub fn get_market_depth(&self, levels: usize) -> (Vec<(Price, Quantity)>, Vec<(Price, Quantity)>) {
-        let bids: Vec<(Price, Quantity)> = self.buy_orders
-            .iter()
-            .rev()
-            .take(levels)
-            .map(|(&price, orders)| {
-                let total_quantity = orders.iter().map(|o| o.remaining_quantity()).sum();
-                (price, total_quantity)
-            })
-            .collect();
+    // Feeds everything that decides how the book trades next into `state`: both
+    // sides and both trigger books in priority order, the last trade price and
+    // whether an auction is running
+    pub fn hash_state<H: Hasher>(&self, state: &mut H) {
+        for levels in [&self.buy_orders.levels, &self.sell_orders.levels, &self.buy_stops, &self.sell_stops] {
+            levels.len().hash(state);
+            for (price, level) in levels {
+                price.hash(state);
+                level.len().hash(state);
+                for key in level.iter(&self.orders) {
+                    self.orders[key].hash(state);
+                }
+            }
+        }
+        self.last_trade_price.hash(state);
+        self.in_auction.hash(state);
+    }
 
-        let asks: Vec<(Price, Quantity)> = self.sell_orders
-            .iter()
-            .take(levels)
-            .map(|(&price, orders)| {
-                let total_quantity = orders.iter().map(|o| o.remaining_quantity()).sum();
-                (price, total_quantity)
+    pub fn snapshot(&self) -> BookSnapshot {
+        let queued = |levels: &BTreeMap<Price, OrderQueue>| -> Vec<Order> {
+            levels
+                .values()
+                .flat_map(|level| level.iter(&self.orders))
+                .map(|key| self.orders[key].clone())
+                .collect()
+        };
+        BookSnapshot {
+            bids: queued(&self.buy_orders.levels),
+            asks: queued(&self.sell_orders.levels),
+            buy_stops: queued(&self.buy_stops),
+            sell_stops: queued(&self.sell_stops),
+            closed_orders: self.orders.iter().filter(|o| !o.is_active()).cloned().collect(),
+            last_trade_price: self.last_trade_price,
+            in_auction: self.in_auction,
+        }
+    }
+
+    // Replaces every order in the book with the snapshot's. The matching policy,
+    // eviction setting and order feed stay as they are; feed subscribers are sent
+    // a snapshot of the restored book.
+    pub fn restore(&mut self, snapshot: BookSnapshot) {
+        *self = Self {
+            matching_policy: self.matching_policy.clone(),
+            ids: self.ids.clone(),
+            evict_closed_orders: self.evict_closed_orders,
+            order_feed: std::mem::take(&mut self.order_feed),
+            circuit_breaker: self.circuit_breaker,
+            ..Self::new()
+        };
+        for order in snapshot.closed_orders {
+            self.orders.insert(order);
+        }
+        // Resting orders keep their displayed slices as saved
+        for order in snapshot.bids.into_iter().chain(snapshot.asks) {
+            let (side, price) = (order.side, order.price);
+            let key = self.orders.insert(order);
+            let ladder = match side {
+                OrderSide::Buy => &mut self.buy_orders,
+                OrderSide::Sell => &mut self.sell_orders,
+            };
+            ladder.push(&mut self.orders, price, key);
+        }
+        for order in snapshot.buy_stops.into_iter().chain(snapshot.sell_stops) {
+            self.add_stop_order(order);
+        }
+        self.last_trade_price = snapshot.last_trade_price;
+        self.in_auction = snapshot.in_auction;
+        let (bids, asks) = (self.resting_orders(OrderSide::Buy), self.resting_orders(OrderSide::Sell));
+        self.order_feed.resync(bids, asks);
+    }
+
+    // Every order resting on one side with the size it shows, best price first
+    // and in time priority within a price
+    pub fn resting_orders(&self, side: OrderSide) -> Vec<RestingOrder> {
+        let ladder = match side {
+            OrderSide::Buy => &self.buy_orders,
+            OrderSide::Sell => &self.sell_orders,
+        };
+        ladder
+            .levels()
+            .flat_map(|(_, level)| level.iter(&self.orders))
+            .map(|key| {
+                let order = &self.orders[key];
+                RestingOrder {
+                    order_id: order.id,
+                    price: order.price,
+                    quantity: order.visible_quantity(),
+                }
             })
-            .collect();
+            .collect()
+    }
+
+    pub fn get_market_depth(&self, levels: usize) -> (DepthLevels, DepthLevels) {
+        let depth = |ladder: &Ladder| -> DepthLevels {
+            ladder
+                .levels()
+                .take(levels)
+                .map(|(&price, level)| {
+                    let total_quantity = level.iter(&self.orders).map(|key| self.orders[key].visible_quantity()).sum();
+                    (price, total_quantity)
+                })
+                .collect()
+        };
 
-        (bids, asks)
+        (depth(&self.buy_orders), depth(&self.sell_orders))
     }
 }
 
@@ -359,3 +1155,212 @@ impl Default for OrderBook {
         Self::new()
     }
 }
+
+// What match_level needs from the book besides its orders
+struct Matcher<'a> {
+    policy: &'a dyn MatchingPolicy,
+    ids: &'a dyn IdGenerator,
+    feed: &'a mut L3Publisher,
+    circuit_breaker: Option<&'a CircuitBreaker>,
+    price_window: &'a mut PriceWindow,
+}
+
+impl Matcher<'_> {
+    // Feeds a trade to the circuit breaker, returning the breach if it tripped
+    fn record(&mut self, trade: &Trade) -> Option<Breach> {
+        let reference_price = self.price_window.record(self.circuit_breaker?, trade.timestamp, trade.price)?;
+        Some(Breach {
+            trade_id: trade.id,
+            trade_price: trade.price,
+            reference_price,
+        })
+    }
+}
+
+// Takes `key` out of the queue at `price`, dropping the level once it is empty
+fn remove_key(levels: &mut BTreeMap<Price, OrderQueue>, orders: &mut OrderArena, price: Price, key: OrderKey) {
+    if let Some(level) = levels.get_mut(&price) {
+        level.remove(orders, key);
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+    }
+}
+
+// Matches the incoming order against one price level. The level is read in time
+// priority, dropping expired orders and settling self-trades as they come up,
+// until the matching policy has every order it needs to split the incoming
+// quantity. Used-up iceberg slices are refreshed at the back of the level and
+// allocation repeats while both sides still have quantity. Resting orders that
+// close go to `closed` when the book evicts them.
+fn match_level(
+    incoming: &mut Order,
+    level: &mut OrderQueue,
+    price: Price,
+    matcher: &mut Matcher,
+    orders: &mut OrderArena,
+    mut closed: Option<&mut Vec<Order>>,
+    execution: &mut Execution,
+) {
+    while incoming.remaining_quantity() > 0 && incoming.is_active() && execution.breach.is_none() {
+        // The orders that can trade and their visible quantity; only the
+        // displayed slice of an iceberg can be hit at a time
+        let mut eligible = Vec::new();
+        let mut visible = Vec::new();
+        let mut covered: Quantity = 0;
+
+        let mut cursor = level.front();
+        while let Some(key) = cursor {
+            if !incoming.is_active() || (!matcher.policy.uses_whole_level() && covered >= incoming.remaining_quantity()) {
+                break;
+            }
+            cursor = orders.next(key);
+            let resting = &mut orders[key];
+
+            // Resting orders past their time in force are dropped instead of traded
+            if resting.is_expired(incoming.timestamp) {
+                resting.status = OrderStatus::Expired;
+                execution.reports.push(ExecutionReport::new(resting, ExecutionType::Expired, execution.timestamp));
+                matcher.feed.publish(OrderEventKind::Delete, resting, resting.visible_quantity(), execution.timestamp);
+                level.remove(orders, key);
+                let expired = orders.remove(key);
+                if let Some(closed) = closed.as_deref_mut() {
+                    closed.push(expired);
+                }
+                continue;
+            }
+
+            if incoming.prevents_trade_with(resting) {
+                let shown = resting.visible_quantity();
+                let cancelled = prevent_self_trade(incoming, resting, execution);
+                if cancelled {
+                    matcher.feed.publish(OrderEventKind::Delete, resting, shown, execution.timestamp);
+                } else if resting.visible_quantity() < shown {
+                    let reduced = shown - resting.visible_quantity();
+                    matcher.feed.publish(OrderEventKind::Reduce, resting, reduced, execution.timestamp);
+                }
+                if cancelled {
+                    level.remove(orders, key);
+                    let cancelled = orders.remove(key);
+                    if let Some(closed) = closed.as_deref_mut() {
+                        closed.push(cancelled);
+                    }
+                }
+            } else {
+                eligible.push(key);
+                visible.push(resting.visible_quantity());
+                covered += resting.visible_quantity();
+            }
+        }
+        if !incoming.is_active() {
+            break;
+        }
+
+        let allocations = matcher.policy.allocate(incoming.remaining_quantity(), &visible);
+        let mut traded = Vec::new();
+        for (&key, allocation) in eligible.iter().zip(allocations) {
+            let resting = &mut orders[key];
+            let trade_quantity = allocation
+                .min(resting.visible_quantity())
+                .min(incoming.remaining_quantity());
+            if trade_quantity == 0 {
+                continue;
+            }
+
+            let (id, now) = (matcher.ids.next_id(), execution.timestamp);
+            let trade = match incoming.side {
+                OrderSide::Buy => Trade::new(incoming, resting, Some(OrderSide::Buy), price, trade_quantity, id, now),
+                OrderSide::Sell => Trade::new(resting, incoming, Some(OrderSide::Sell), price, trade_quantity, id, now),
+            };
+            incoming.fill(trade_quantity, price);
+            resting.fill(trade_quantity, price);
+            execution.reports.push(ExecutionReport::fill(incoming, &trade));
+            execution.reports.push(ExecutionReport::fill(resting, &trade));
+            matcher.feed.publish(OrderEventKind::Execute, resting, trade_quantity, now);
+            execution.breach = matcher.record(&trade);
+            execution.trades.push(trade);
+            traded.push(key);
+            if execution.breach.is_some() {
+                break;
+            }
+        }
+        if traded.is_empty() {
+            break;
+        }
+
+        // Filled orders leave the level; iceberg slices that ran out show a new one
+        // from the reserve and go to the back of the queue at this price
+        for key in traded {
+            let resting = &mut orders[key];
+            if resting.is_complete() {
+                level.remove(orders, key);
+                if let Some(closed) = closed.as_deref_mut() {
+                    closed.push(orders.remove(key));
+                }
+            } else if resting.visible_quantity() == 0 {
+                resting.refresh_display();
+                matcher.feed.publish(OrderEventKind::Add, resting, resting.visible_quantity(), execution.timestamp);
+                level.remove(orders, key);
+                level.push_back(orders, key);
+            }
+        }
+    }
+}
+
+// Applies the incoming order's self-trade prevention mode in place of a trade
+// against `resting`, reporting every order it cancels or reduces. Returns whether
+// the resting order was cancelled.
+fn prevent_self_trade(incoming: &mut Order, resting: &mut Order, execution: &mut Execution) -> bool {
+    let mode = incoming.self_trade_prevention.unwrap_or(SelfTradePrevention::CancelNewest);
+    let (incoming_affected, resting_affected) = match mode {
+        SelfTradePrevention::CancelNewest => (true, false),
+        SelfTradePrevention::CancelOldest => (false, true),
+        SelfTradePrevention::CancelBoth => (true, true),
+        SelfTradePrevention::DecrementAndCancel => {
+            let quantity = incoming.remaining_quantity().min(resting.remaining_quantity());
+            incoming.decrement(quantity);
+            resting.decrement(quantity);
+            (true, true)
+        }
+    };
+
+    let cancel = |order: &mut Order| {
+        if mode != SelfTradePrevention::DecrementAndCancel || order.remaining_quantity() == 0 {
+            order.status = OrderStatus::Cancelled;
+        }
+    };
+    if incoming_affected {
+        cancel(incoming);
+    }
+    if resting_affected {
+        cancel(resting);
+    }
+
+    let reason = format!(
+        "self-trade between orders {} and {} prevented ({:?})",
+        incoming.id, resting.id, mode
+    );
+    for (order, affected) in [(&*incoming, incoming_affected), (&*resting, resting_affected)] {
+        if affected {
+            execution.reports.push(ExecutionReport {
+                reason: Some(reason.clone()),
+                ..ExecutionReport::new(order, ExecutionType::SelfTradePrevented, execution.timestamp)
+            });
+        }
+    }
+
+    resting.status == OrderStatus::Cancelled
+}
+
+// Orders the book cannot take at all, whatever state it is in
+fn rejection_reason(order: &Order) -> Option<&'static str> {
+    if order.remaining_quantity() == 0 {
+        Some("order has no quantity left to trade")
+    } else if order.is_stop() && order.stop_price.is_none() {
+        Some("stop order has no stop price")
+    } else if order.peak_quantity == Some(0) {
+        Some("iceberg peak quantity is zero")
+    } else {
+        None
+    }
+}
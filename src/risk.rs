@@ -0,0 +1,182 @@
+use crate::account::{Account, Cash};
+use crate::order_book::{Order, OrderSide, OrderType, Price, Quantity};
+use std::fmt;
+
+// Why a pre-trade risk check refused an order. Money and prices are in the
+// instrument's fixed-point price units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskRejection {
+    InsufficientBuyingPower { required: Cash, available: Cash },
+    PositionLimitExceeded { projected_position: i64, limit: i64 },
+    ShortLimitExceeded { projected_position: i64, limit: i64 },
+    OrderQuantityTooLarge { quantity: Quantity, limit: Quantity },
+    OrderNotionalTooLarge { notional: Cash, limit: Cash },
+    PriceOutsideBand { price: Price, reference_price: Price, band_percentage: f64 },
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskRejection::InsufficientBuyingPower { required, available } => write!(
+                f,
+                "Insufficient buying power: order needs {} but only {} is available",
+                required, available
+            ),
+            RiskRejection::PositionLimitExceeded { projected_position, limit } => write!(
+                f,
+                "Order would take the position to {}, above the limit of {}",
+                projected_position, limit
+            ),
+            RiskRejection::ShortLimitExceeded { projected_position, limit } => write!(
+                f,
+                "Order would take the position to {}, beyond the short limit of {}",
+                projected_position, limit
+            ),
+            RiskRejection::OrderQuantityTooLarge { quantity, limit } => {
+                write!(f, "Order quantity {} exceeds the maximum of {}", quantity, limit)
+            }
+            RiskRejection::OrderNotionalTooLarge { notional, limit } => {
+                write!(f, "Order notional {} exceeds the maximum of {}", notional, limit)
+            }
+            RiskRejection::PriceOutsideBand { price, reference_price, band_percentage } => write!(
+                f,
+                "Price {} is more than {:.2}% away from the reference price {}",
+                price, band_percentage, reference_price
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RiskRejection {}
+
+// What a risk check gets to see about the market and the submitting account
+#[derive(Debug, Clone, Copy)]
+pub struct RiskContext<'a> {
+    pub account: Option<&'a Account>,
+    // Exposure from the account's other resting and stop orders
+    pub open_buy_notional: Cash,
+    pub open_buy_quantity: Quantity,
+    pub open_sell_quantity: Quantity,
+    pub last_trade_price: Option<Price>,
+    pub best_bid: Option<Price>,
+    pub best_ask: Option<Price>,
+}
+
+impl RiskContext<'_> {
+    // Last trade, or the mid price if nothing has traded yet
+    pub fn reference_price(&self) -> Option<Price> {
+        self.last_trade_price.or(match (self.best_bid, self.best_ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2),
+            _ => None,
+        })
+    }
+
+    // Price the order is expected to trade at: its limit or stop price, or for a
+    // market order the touch on the opposite side (falling back to the last trade)
+    pub fn expected_price(&self, order: &Order) -> Option<Price> {
+        match order.order_type {
+            OrderType::Limit | OrderType::StopLimit => Some(order.price),
+            OrderType::Stop => order.stop_price,
+            OrderType::Market => match order.side {
+                OrderSide::Buy => self.best_ask,
+                OrderSide::Sell => self.best_bid,
+            }
+            .or(self.last_trade_price),
+        }
+    }
+}
+
+// Price times quantity, saturating at Cash::MAX, so an order too large to value
+// is over every limit instead of overflowing
+pub fn notional(price: Price, quantity: Quantity) -> Cash {
+    price.saturating_mul(Cash::try_from(quantity).unwrap_or(Cash::MAX))
+}
+
+// A pre-trade check run by MarketSimulator on every order before it reaches the book
+pub trait RiskCheck: fmt::Debug {
+    fn check(&self, order: &Order, context: &RiskContext) -> Result<(), RiskRejection>;
+}
+
+// Open and new quantity together as a position change, saturating so that an
+// oversized order shows up as beyond any limit instead of wrapping
+fn shares(open: Quantity, quantity: Quantity) -> i64 {
+    i64::try_from(open.saturating_add(quantity)).unwrap_or(i64::MAX)
+}
+
+// The standard set of limits; every limit is off unless set
+#[derive(Debug, Clone, Default)]
+pub struct RiskLimits {
+    // Buys must be covered by cash not already committed to other buys
+    pub check_buying_power: bool,
+    pub max_long_position: Option<i64>,
+    // Largest allowed short position, as a positive number; 0 means sells must be
+    // covered by inventory
+    pub max_short_position: Option<i64>,
+    pub max_order_quantity: Option<Quantity>,
+    pub max_order_notional: Option<Cash>,
+    // Limit prices must be within this percentage of the reference price
+    pub price_band_percentage: Option<f64>,
+}
+
+impl RiskCheck for RiskLimits {
+    fn check(&self, order: &Order, context: &RiskContext) -> Result<(), RiskRejection> {
+        let quantity = order.remaining_quantity();
+        let notional = context.expected_price(order).map_or(0, |price| notional(price, quantity));
+
+        if let Some(limit) = self.max_order_quantity {
+            if quantity > limit {
+                return Err(RiskRejection::OrderQuantityTooLarge { quantity, limit });
+            }
+        }
+
+        if let Some(limit) = self.max_order_notional {
+            if notional > limit {
+                return Err(RiskRejection::OrderNotionalTooLarge { notional, limit });
+            }
+        }
+
+        if let (Some(band_percentage), OrderType::Limit | OrderType::StopLimit) =
+            (self.price_band_percentage, order.order_type)
+        {
+            if let Some(reference_price) = context.reference_price() {
+                let deviation = (order.price - reference_price).abs() as f64 / reference_price as f64 * 100.0;
+                if deviation > band_percentage {
+                    return Err(RiskRejection::PriceOutsideBand {
+                        price: order.price,
+                        reference_price,
+                        band_percentage,
+                    });
+                }
+            }
+        }
+
+        let position = context.account.map_or(0, |account| account.position);
+        match order.side {
+            OrderSide::Buy => {
+                if self.check_buying_power {
+                    let cash = context.account.map_or(0, |account| account.cash);
+                    let available = cash.saturating_sub(context.open_buy_notional);
+                    if notional > available {
+                        return Err(RiskRejection::InsufficientBuyingPower { required: notional, available });
+                    }
+                }
+                if let Some(limit) = self.max_long_position {
+                    let projected_position = position.saturating_add(shares(context.open_buy_quantity, quantity));
+                    if projected_position > limit {
+                        return Err(RiskRejection::PositionLimitExceeded { projected_position, limit });
+                    }
+                }
+            }
+            OrderSide::Sell => {
+                if let Some(limit) = self.max_short_position {
+                    let projected_position = position.saturating_sub(shares(context.open_sell_quantity, quantity));
+                    if projected_position < -limit {
+                        return Err(RiskRejection::ShortLimitExceeded { projected_position, limit });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}